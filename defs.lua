@@ -1,30 +1,53 @@
 ---@meta
 
+-- generated from rust types by `factorio-crater lua-defs`, don't edit
 ---@class Mod
 ---@field category string
 ---@field changelog string?
 ---@field created_at string
----@field downloads_count string
+---@field downloads_count integer
 ---@field deprecated boolean
----@field description string
+---@field description string?
 ---@field homepage string
 ---@field images Image[]
 ---@field license License?
 ---@field name string
 ---@field owner string
 ---@field releases Release[]
----@field score number?
+---@field score number
 ---@field source_url string?
 ---@field summary string
 ---@field tags string[]?
 ---@field thumbnail string?
 ---@field title string
 ---@field updated_at string
+local Mod = {}
+
+---@class ModShort
+---@field name string
+---@field latest_release LatestRelease?
+local ModShort = {}
+
+---@class Changelog
+---@field versions ChangelogVersion[]
+local Changelog = {}
+
+---@class ChangelogVersion
+---@field version string
+---@field date string?
+---@field categories ChangelogCategory[]
+local ChangelogVersion = {}
+
+---@class ChangelogCategory
+---@field name string
+---@field entries string[]
+local ChangelogCategory = {}
 
 ---@class Image
 ---@field id string
 ---@field thumbnail string
 ---@field url string
+local Image = {}
 
 ---@class License
 ---@field description string
@@ -32,24 +55,67 @@
 ---@field name string
 ---@field title string
 ---@field url string
+local License = {}
 
 ---@class Release
 ---@field download_url string
 ---@field file_name string
 ---@field info_json InfoJson
----@field release_at string
+---@field released_at string
 ---@field sha1 string
 ---@field version string
+local Release = {}
 
 ---@class InfoJson
 ---@field dependencies Dep[]
 ---@field factorio_version string
+local InfoJson = {}
+
+---@class LatestRelease
+---@field download_url string
+---@field file_name string
+---@field info_json ShortInfoJson
+---@field released_at string
+---@field sha1 string
+---@field version string
+local LatestRelease = {}
+
+---@class ShortInfoJson
+---@field factorio_version string
+local ShortInfoJson = {}
 
 ---@class Dep
 ---@field original string
 ---@field prefix Prefix
 ---@field name string
 ---@field version string
+local Dep = {}
+
+---@class Version
+---@field major integer
+---@field minor integer
+---@field sub integer
+local Version = {}
+
+---@class BrokenReport
+---@field deprecated string[]
+---@field working string[]
+---@field broken table<string, string[]> broken mods, with their deprecated, broken, or unavailable dependencies
+---@field missing_deps table<string, string> mods requiring a mod that doesn't exist, with that mod
+---@field unresolved string[] mods in dependency cycles
+---@field uninstallable table<string, string[]> working mods whose dependencies are incompatible with each other
+local BrokenReport = {}
+-- end of generated definitions
+
+---Parses changelog in factorio's format.
+---@return Changelog? changelog
+---@return string? error why factorio would reject it
+function Mod:parse_changelog() end
+
+---Versions newer than given one.
+---@param version string
+---@return ChangelogVersion[]
+function Changelog:since(version) end
 
 ---@alias Prefix
 ---| "incompatible"
@@ -58,4 +124,80 @@
 ---| "load-order-independent"
 ---| "required"
 
-_G.mods = mods ---@type Mod[]
+---@param name string
+---@return "working"|"broken"|"deprecated"|"missing"|"incompatible"|"built-in"|"unresolved"
+function BrokenReport:status(name) end
+
+---Why a mod isn't working.
+---@param name string
+---@return string[]
+function BrokenReport:reasons(name) end
+
+crater = {}
+
+---Mod with given name.
+---@param name string
+---@return Mod?
+function crater.get(name) end
+
+---@param version string
+---@return Version? version
+---@return string? error
+function crater.parse_version(version) end
+
+---Whether version matches version requirement of a dependency.
+---@param dep Dep|string dependency, or dependency string like "foo >= 1.2"
+---@param version Version|string
+---@return boolean
+function crater.version_satisfies(dep, version) end
+
+---Mods whose latest release requires given mod.
+---@param name string
+---@return string[]
+function crater.rdeps(name) end
+
+---Chooses releases of given mods and everything they require.
+---@param names string[]
+---@param factorio_version Version|string
+---@param space_age boolean? defaults to true
+---@return table<string, string>? versions of chosen releases, without built-in mods
+---@return string? error
+function crater.resolve(names, factorio_version, space_age) end
+
+---Same analysis as `find-broken-mods` command.
+---@param space_age boolean? defaults to true
+---@return BrokenReport
+function crater.broken_report(space_age) end
+
+---Encodes value as JSON. Tables with keys `1..n` become arrays, others become
+---objects with sorted keys; userdata is written through `tostring`.
+---@param value any
+---@param pretty boolean? defaults to false
+---@return string
+function crater.to_json(value, pretty) end
+
+---Writes value as pretty JSON into file.
+---@param path string
+---@param value any
+function crater.write_json(path, value) end
+
+---Writes rows as CSV into file. Rows that are sequences are written without
+---header; named rows are written in order of `columns` (all their keys,
+---sorted, if not given) after a header.
+---@param path string
+---@param rows table[]
+---@param columns string[]?
+function crater.write_csv(path, rows, columns) end
+
+---@param path string
+---@param text string
+function crater.write_text(path, text) end
+
+---Cached mods, also by name, each loaded on first use.
+_G.mods = mods ---@type Mod[]|table<string, Mod>
+
+---Mods from the mod list, also by name.
+_G.mods_short = mods_short ---@type ModShort[]|table<string, ModShort>
+
+---Script's path at index 0, and arguments given after `--`.
+_G.arg = arg ---@type table<integer, string>
@@ -0,0 +1,66 @@
+#!/bin/sh
+# Stands in for headless factorio in `factorio-crater crater-run`, printing
+# log lines like factorio does, without loading anything:
+#
+#   factorio-crater crater-run --factorio-bin ./factorio-stub.sh --mods a,b
+#
+# Factorio version is taken from STUB_VERSION (2.0.28 by default). Mods named
+# in STUB_LUA_ERROR fail with lua error, mods named in STUB_MISSING_DEP with
+# missing dependency, and mods named in STUB_HANG never finish loading (names
+# are separated by spaces).
+
+version=${STUB_VERSION:-2.0.28}
+
+if [ "$1" = "--version" ]; then
+    echo "Version: $version (build 80345, linux64, headless)"
+    echo "Binary version: 64"
+    exit 0
+fi
+
+mod_dir=
+while [ $# -gt 0 ]; do
+    case "$1" in
+        --mod-directory) mod_dir=$2; shift ;;
+    esac
+    shift
+done
+
+echo "   0.000 2024-12-10 12:00:00; Factorio $version (build 80345, linux64, headless)"
+echo "   0.001 Operating system: Linux"
+
+# enabled mods, in order of mod-list.json
+mods=$(awk -F '"' '
+    /"name"/ { name = $4 }
+    /"enabled": *true/ { print name }
+' "$mod_dir/mod-list.json")
+
+listed() {
+    for x in $2; do
+        [ "$x" = "$1" ] && return 0
+    done
+    return 1
+}
+
+echo "   0.010 Loading mod core 0.0.0 (data.lua)"
+for name in $mods; do
+    if listed "$name" "$STUB_MISSING_DEP"; then
+        echo "   0.020 Error Util.cpp:83: Failed to load mods: Mod $name (1.0.0) is missing required dependency stub-dependency"
+        exit 1
+    fi
+done
+for name in $mods; do
+    echo "   0.030 Loading mod $name 1.0.0 (data.lua)"
+    if listed "$name" "$STUB_LUA_ERROR"; then
+        echo "   0.040 Error ModManager.cpp:1560: Failed to load mod \"$name\": __${name}__/data.lua:3: attempt to index global 'stub' (a nil value)"
+        echo "stack traceback:"
+        echo "	[C]: in function 'error'"
+        echo "	__${name}__/data.lua:3: in main chunk"
+        exit 1
+    fi
+    if listed "$name" "$STUB_HANG"; then
+        while :; do sleep 1; done
+    fi
+done
+echo "   0.050 Checksum for core: 1234567890"
+echo "   0.060 Creating new map"
+echo "   0.070 Goodbye"
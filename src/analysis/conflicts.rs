@@ -0,0 +1,195 @@
+use core::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    Error, INTERNAL_MODS,
+    cache::Cache,
+    deserialization::{Dep, DepPrefix},
+};
+
+/// Incompatibility declared by `declared_by` with `incompatible`, where both
+/// of them would have to be loaded together.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Conflict {
+    pub declared_by: String,
+    pub incompatible: String,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} is incompatible with {}",
+            self.declared_by, self.incompatible
+        )
+    }
+}
+
+/// Returns `roots` together with all mods they transitively require.
+///
+/// Mods without known dependencies (like built-in ones) are included, but not
+/// followed further.
+pub fn required_closure<'a>(
+    roots: impl IntoIterator<Item = &'a str>,
+    deps_of: impl Fn(&str) -> Option<&'a [Dep]>,
+) -> BTreeSet<&'a str> {
+    let mut closure = BTreeSet::new();
+    let mut queue: Vec<&str> = roots.into_iter().collect();
+    while let Some(name) = queue.pop() {
+        if !closure.insert(name) {
+            continue;
+        }
+        queue.extend(
+            deps_of(name)
+                .unwrap_or_default()
+                .iter()
+                .filter(|x| matches!(x.prefix, DepPrefix::Required))
+                .map(|x| x.name.as_str()),
+        );
+    }
+    closure
+}
+
+/// Lists every incompatibility declared between mods of given set.
+pub fn find_conflicts<'a>(
+    mods: &BTreeSet<&'a str>,
+    deps_of: impl Fn(&str) -> Option<&'a [Dep]>,
+) -> Vec<Conflict> {
+    let mut conflicts = vec![];
+    for &name in mods {
+        for dep in deps_of(name).unwrap_or_default() {
+            if matches!(dep.prefix, DepPrefix::Incompatible)
+                && mods.contains(dep.name.as_str())
+            {
+                conflicts.push(Conflict {
+                    declared_by: name.to_string(),
+                    incompatible: dep.name.clone(),
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+/// Result of [`mod_conflicts`].
+#[derive(Debug, Clone, Default)]
+pub struct ModConflicts {
+    /// given or required mods that aren't cached
+    pub unknown: Vec<String>,
+    /// mods required by given ones, which weren't given
+    pub required: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Finds incompatibilities between given mods and everything they require,
+/// using dependencies of their latest releases.
+pub fn mod_conflicts(
+    cache: &Cache,
+    mods: &[String],
+) -> Result<ModConflicts, Error> {
+    let mut result = ModConflicts::default();
+    let mut deps = BTreeMap::<String, Vec<Dep>>::new();
+    let mut queue = mods.to_vec();
+    while let Some(name) = queue.pop() {
+        if deps.contains_key(&name) || INTERNAL_MODS.contains(&name.as_str()) {
+            continue;
+        }
+        let m = match cache.load_mod(&name) {
+            Ok(m) => m,
+            Err(Error::NotCached(_)) => {
+                if !result.unknown.contains(&name) {
+                    result.unknown.push(name);
+                }
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let release_deps = m
+            .latest_release()
+            .map(|x| x.info_json.dependencies.clone())
+            .unwrap_or_default();
+        queue.extend(
+            release_deps
+                .iter()
+                .filter(|x| matches!(x.prefix, DepPrefix::Required))
+                .map(|x| x.name.clone()),
+        );
+        deps.insert(name, release_deps);
+    }
+
+    let deps_of = |name: &str| deps.get(name).map(Vec::as_slice);
+    let closure = required_closure(mods.iter().map(|x| x.as_str()), deps_of);
+    result.required = closure
+        .iter()
+        .filter(|name| !mods.iter().any(|x| x == *name))
+        .map(|x| x.to_string())
+        .collect();
+    result.conflicts = find_conflicts(&closure, deps_of);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Dependencies of mods, as `(name, dependency strings)`.
+    fn deps(mods: &[(&str, &[&str])]) -> BTreeMap<String, Vec<Dep>> {
+        mods.iter()
+            .map(|(name, deps)| {
+                let deps = deps.iter().map(|x| x.parse().unwrap()).collect();
+                (name.to_string(), deps)
+            })
+            .collect()
+    }
+
+    fn conflicts_of(
+        deps: &BTreeMap<String, Vec<Dep>>,
+        root: &str,
+    ) -> (Vec<String>, Vec<Conflict>) {
+        let deps_of = |name: &str| deps.get(name).map(Vec::as_slice);
+        let closure = required_closure([root], deps_of);
+        let conflicts = find_conflicts(&closure, deps_of);
+        (closure.into_iter().map(Into::into).collect(), conflicts)
+    }
+
+    fn conflict(declared_by: &str, incompatible: &str) -> Conflict {
+        Conflict {
+            declared_by: declared_by.into(),
+            incompatible: incompatible.into(),
+        }
+    }
+
+    #[test]
+    fn dependency_incompatible_with_another() {
+        let deps = deps(&[("mod", &["a", "b"]), ("a", &[]), ("b", &["! a"])]);
+        let (closure, conflicts) = conflicts_of(&deps, "mod");
+        assert_eq!(closure, ["a", "b", "mod"]);
+        assert_eq!(conflicts, [conflict("b", "a")]);
+    }
+
+    #[test]
+    fn conflict_through_transitive_dependency() {
+        let deps = deps(&[
+            ("mod", &["a", "b >= 1.0"]),
+            ("a", &["c"]),
+            ("b", &["! c"]),
+            ("c", &["base"]),
+        ]);
+        let (closure, conflicts) = conflicts_of(&deps, "mod");
+        assert_eq!(closure, ["a", "b", "base", "c", "mod"]);
+        assert_eq!(conflicts, [conflict("b", "c")]);
+    }
+
+    #[test]
+    fn optional_dependencies_are_not_followed() {
+        let deps = deps(&[
+            ("mod", &["a", "? c", "(?) d"]),
+            ("a", &["! c", "! d"]),
+            ("c", &[]),
+            ("d", &[]),
+        ]);
+        let (closure, conflicts) = conflicts_of(&deps, "mod");
+        assert_eq!(closure, ["a", "mod"]);
+        assert!(conflicts.is_empty());
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::Write,
+    path::Path,
+};
+
+use crate::{
+    Error,
+    analysis::conflicts::{Conflict, find_conflicts, required_closure},
+    deserialization::{Dep, DepPrefix, ModFull},
+    internal_mods,
+    version::Version,
+};
+
+/// Compatibility of all mods with a single factorio version, taking every
+/// release into account instead of only the latest one.
+pub struct VersionCompat {
+    pub factorio_version: Version,
+    /// mods with at least one release for this version
+    pub mods: BTreeSet<String>,
+    pub deprecated: BTreeSet<String>,
+    /// mods with at least one release whose required dependencies can be
+    /// satisfied, without relying on deprecated mods
+    pub working: BTreeSet<String>,
+    /// mods that only worked thanks to a deprecated mod, with the deprecated
+    /// or broken dependencies that caused it
+    pub broken_by_deprecation: BTreeMap<String, Vec<String>>,
+    /// mods whose dependencies were never satisfiable for this version
+    pub broken: BTreeSet<String>,
+    /// mods whose required dependencies include mutually incompatible mods
+    pub incompatible: BTreeMap<String, Vec<Conflict>>,
+}
+
+impl VersionCompat {
+    pub fn status(&self, name: &str) -> Option<&'static str> {
+        if !self.mods.contains(name) {
+            None
+        } else if self.deprecated.contains(name) {
+            Some("deprecated")
+        } else if self.working.contains(name) {
+            Some("working")
+        } else if self.incompatible.contains_key(name) {
+            Some("incompatible")
+        } else if self.broken_by_deprecation.contains_key(name) {
+            Some("broken by deprecation")
+        } else {
+            Some("broken")
+        }
+    }
+}
+
+struct ReleaseInfo<'a> {
+    version: Version,
+    dependencies: &'a [Dep],
+}
+
+/// `major.minor` part of factorio version, like `1.1`.
+pub fn short_version(version: Version) -> String {
+    format!("{}.{}", version.major, version.minor)
+}
+
+fn required_deps(deps: &[Dep]) -> impl Iterator<Item = &Dep> {
+    deps.iter()
+        .filter(|x| matches!(x.prefix, DepPrefix::Required))
+}
+
+/// Returns, for every mod, versions of its releases that can be loaded.
+///
+/// Iterates until no more releases can be marked as working, as dependencies
+/// can appear in any order.
+fn working_releases<'a>(
+    releases: &BTreeMap<&'a str, Vec<ReleaseInfo<'a>>>,
+    internal: &[&str],
+    blocked: &BTreeSet<&str>,
+) -> BTreeMap<&'a str, Vec<Version>> {
+    let mut working = BTreeMap::<&str, Vec<Version>>::new();
+    let mut pending: Vec<(&str, &ReleaseInfo)> = releases
+        .iter()
+        .filter(|(name, _)| !blocked.contains(*name))
+        .flat_map(|(&name, rs)| rs.iter().map(move |r| (name, r)))
+        .collect();
+    loop {
+        let before = pending.len();
+        pending.retain(|&(name, release)| {
+            let satisfied = required_deps(release.dependencies).all(|dep| {
+                if internal.contains(&dep.name.as_str()) {
+                    return true;
+                }
+                working.get(dep.name.as_str()).is_some_and(|versions| {
+                    versions
+                        .iter()
+                        .any(|&v| dep.version.is_none_or(|req| req.matches(v)))
+                })
+            });
+            if satisfied {
+                working.entry(name).or_default().push(release.version);
+            }
+            !satisfied
+        });
+        if pending.len() == before {
+            break;
+        }
+    }
+    working
+}
+
+fn analyze_version(
+    factorio_version: Version,
+    releases: &BTreeMap<&str, Vec<ReleaseInfo>>,
+    deprecated_mods: &BTreeSet<&str>,
+    space_age: bool,
+) -> VersionCompat {
+    let internal = internal_mods(factorio_version, space_age);
+    let historic = working_releases(releases, internal, &BTreeSet::new());
+    let current = working_releases(releases, internal, deprecated_mods);
+
+    let mods: BTreeSet<String> =
+        releases.keys().map(|x| x.to_string()).collect();
+    let deprecated: BTreeSet<String> = releases
+        .keys()
+        .filter(|x| deprecated_mods.contains(*x))
+        .map(|x| x.to_string())
+        .collect();
+
+    // only newest working release of each mod is checked for conflicts, as
+    // trying every combination of releases would be too slow
+    let newest_deps: BTreeMap<&str, &[Dep]> = current
+        .iter()
+        .filter_map(|(&name, versions)| {
+            let newest = versions.iter().max()?;
+            let release =
+                releases[name].iter().find(|x| x.version == *newest)?;
+            Some((name, release.dependencies))
+        })
+        .collect();
+    let deps_of = |name: &str| newest_deps.get(name).copied();
+    let mut working = BTreeSet::new();
+    let mut incompatible = BTreeMap::new();
+    for &name in current.keys() {
+        let conflicts =
+            find_conflicts(&required_closure([name], deps_of), deps_of);
+        if conflicts.is_empty() {
+            working.insert(name.to_string());
+        } else {
+            incompatible.insert(name.to_string(), conflicts);
+        }
+    }
+
+    let mut broken_by_deprecation = BTreeMap::new();
+    let mut broken = BTreeSet::new();
+    for &name in releases.keys() {
+        if deprecated_mods.contains(name) || current.contains_key(name) {
+            continue;
+        }
+        if !historic.contains_key(name) {
+            broken.insert(name.to_string());
+            continue;
+        }
+        let reasons: BTreeSet<String> = releases[name]
+            .iter()
+            .flat_map(|x| required_deps(x.dependencies))
+            .filter(|x| {
+                historic.contains_key(x.name.as_str())
+                    && !current.contains_key(x.name.as_str())
+            })
+            .map(|x| x.name.clone())
+            .collect();
+        broken_by_deprecation
+            .insert(name.to_string(), reasons.into_iter().collect());
+    }
+
+    VersionCompat {
+        factorio_version,
+        mods,
+        deprecated,
+        working,
+        broken_by_deprecation,
+        broken,
+        incompatible,
+    }
+}
+
+/// Builds per factorio version compatibility of all mods, ordered by version.
+///
+/// With `space_age` set to false, mods depending on expansion's built-in mods
+/// are considered broken.
+///
+/// Releases whose versions can't be parsed are skipped, and their errors are
+/// returned alongside.
+pub fn compat_matrix(
+    mod_list: &[ModFull],
+    space_age: bool,
+) -> (Vec<VersionCompat>, Vec<Error>) {
+    let mut errors = Vec::new();
+    let mut by_version =
+        BTreeMap::<Version, BTreeMap<&str, Vec<ReleaseInfo>>>::new();
+    for m in mod_list {
+        for release in &m.releases {
+            let (Ok(factorio_version), Ok(version)) = (
+                release.info_json.factorio_version.parse::<Version>(),
+                release.version.parse::<Version>(),
+            ) else {
+                errors.push(Error::Parse {
+                    mod_name: m.name.clone(),
+                    field: "releases".into(),
+                    message: format!(
+                        "skipping unparsable release {}",
+                        release.version
+                    ),
+                });
+                continue;
+            };
+            by_version
+                .entry(factorio_version.factorio_version())
+                .or_default()
+                .entry(&m.name)
+                .or_default()
+                .push(ReleaseInfo {
+                    version,
+                    dependencies: &release.info_json.dependencies,
+                });
+        }
+    }
+    let deprecated: BTreeSet<&str> = mod_list
+        .iter()
+        .filter(|x| x.deprecated)
+        .map(|x| x.name.as_str())
+        .collect();
+
+    let matrix = by_version
+        .iter()
+        .map(|(&version, releases)| {
+            analyze_version(version, releases, &deprecated, space_age)
+        })
+        .collect();
+    (matrix, errors)
+}
+
+/// Writes `compat_matrix.csv`, `broken_by_deprecation.txt` and
+/// `incompatible_by_version.txt` for compatibility matrix of given mods into
+/// `dir`.
+pub fn write_compat_matrix(
+    matrix: &[VersionCompat],
+    mod_list: &[ModFull],
+    dir: &Path,
+) -> Result<(), Error> {
+    let mut matrix_file = File::create(dir.join("compat_matrix.csv"))?;
+    write!(matrix_file, "name")?;
+    for v in matrix {
+        let factorio_version = short_version(v.factorio_version);
+        write!(matrix_file, ",{factorio_version}")?;
+    }
+    writeln!(matrix_file)?;
+    let mut names: Vec<&str> =
+        mod_list.iter().map(|x| x.name.as_str()).collect();
+    names.sort_unstable();
+    for name in names {
+        write!(matrix_file, "{name}")?;
+        for v in matrix {
+            write!(matrix_file, ",{}", v.status(name).unwrap_or_default())?;
+        }
+        writeln!(matrix_file)?;
+    }
+
+    let mut bbd_file = File::create(dir.join("broken_by_deprecation.txt"))?;
+    for v in matrix {
+        let factorio_version = short_version(v.factorio_version);
+        for (name, broken_deps) in &v.broken_by_deprecation {
+            writeln!(bbd_file, "{name} for {factorio_version} because of:")?;
+            for broken_dep in broken_deps {
+                writeln!(bbd_file, "  {broken_dep}")?;
+            }
+        }
+    }
+
+    let mut i_file = File::create(dir.join("incompatible_by_version.txt"))?;
+    for v in matrix {
+        let factorio_version = short_version(v.factorio_version);
+        for (name, conflicts) in &v.incompatible {
+            writeln!(i_file, "{name} for {factorio_version} because of:")?;
+            for conflict in conflicts {
+                writeln!(i_file, "  {conflict}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mod_full(json: &str) -> ModFull {
+        simd_json::from_slice(&mut json.as_bytes().to_vec()).unwrap()
+    }
+
+    /// Mod with releases given as `(version, factorio version, deps)`.
+    fn with_releases(
+        name: &str,
+        deprecated: bool,
+        releases: &[(&str, &str, &[&str])],
+    ) -> ModFull {
+        let releases = releases
+            .iter()
+            .map(|(version, factorio_version, deps)| {
+                format!(
+                    r#"{{"version": "{version}", "info_json": {{
+                        "factorio_version": "{factorio_version}",
+                        "dependencies": {deps:?}}}}}"#
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        mod_full(&format!(
+            r#"{{"name": "{name}", "deprecated": {deprecated},
+                "releases": [{releases}]}}"#
+        ))
+    }
+
+    fn for_version(
+        matrix: &[VersionCompat],
+        major: u16,
+        minor: u16,
+    ) -> &VersionCompat {
+        let version = Version::new(major, minor, 0);
+        matrix
+            .iter()
+            .find(|x| x.factorio_version == version)
+            .unwrap()
+    }
+
+    #[test]
+    fn older_release_keeps_mod_working() {
+        // newest release dropped 1.1, but the older one still works there
+        let mods = [
+            with_releases(
+                "a",
+                false,
+                &[("1.0.0", "1.1", &["base"]), ("2.0.0", "2.0", &["base"])],
+            ),
+            with_releases(
+                "b",
+                false,
+                &[("1.0.0", "1.1", &["a >= 1.0.0"]), ("1.1.0", "2.0", &[])],
+            ),
+        ];
+        let (matrix, errors) = compat_matrix(&mods, true);
+        assert!(errors.is_empty());
+        assert_eq!(matrix.len(), 2);
+        let v1_1 = for_version(&matrix, 1, 1);
+        assert!(v1_1.working.contains("a"));
+        assert!(v1_1.working.contains("b"));
+        assert_eq!(v1_1.status("a"), Some("working"));
+        assert_eq!(for_version(&matrix, 2, 0).status("a"), Some("working"));
+        // no releases for 1.0
+        assert_eq!(v1_1.status("c"), None);
+    }
+
+    #[test]
+    fn deprecated_dependency_breaks_dependents() {
+        let mods = [
+            with_releases("lib", true, &[("1.0.0", "1.1", &[])]),
+            with_releases("user", false, &[("1.0.0", "1.1", &["lib"])]),
+            with_releases("optional", false, &[("1.0.0", "1.1", &["? lib"])]),
+            with_releases("typo", false, &[("1.0.0", "1.1", &["missing"])]),
+        ];
+        let (matrix, _) = compat_matrix(&mods, true);
+        let v1_1 = for_version(&matrix, 1, 1);
+        assert_eq!(v1_1.status("lib"), Some("deprecated"));
+        assert_eq!(
+            v1_1.broken_by_deprecation.get("user"),
+            Some(&vec!["lib".to_string()])
+        );
+        assert_eq!(v1_1.status("user"), Some("broken by deprecation"));
+        // optional dependencies don't matter
+        assert_eq!(v1_1.status("optional"), Some("working"));
+        // mods that never worked aren't blamed on deprecation
+        assert!(!v1_1.broken_by_deprecation.contains_key("typo"));
+        assert_eq!(v1_1.status("typo"), Some("broken"));
+    }
+}
@@ -0,0 +1,431 @@
+//! Analyses of mod dependencies.
+
+pub mod conflicts;
+pub mod history;
+pub mod resolve;
+
+use core::{fmt, mem};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::Write,
+    path::Path,
+};
+
+use crate::{
+    Error, INTERNAL_MODS, SPACE_AGE_VERSION,
+    analysis::conflicts::{Conflict, find_conflicts, required_closure},
+    cache::Cache,
+    deserialization::{Dep, DepPrefix, Mod, ModFull},
+    internal_mods,
+    version::Version,
+};
+
+/// Info about latest release of a mod.
+#[derive(Debug, Clone)]
+pub struct ModWithInfo {
+    pub deprecated: bool,
+    pub factorio_version: String,
+    pub dependencies: Vec<Dep>,
+}
+
+impl ModWithInfo {
+    /// Info about latest release of a mod, `None` if it has no releases.
+    pub fn latest(m: &ModFull) -> Option<Self> {
+        let release = m.latest_release()?;
+        Some(Self {
+            deprecated: m.deprecated,
+            factorio_version: release.info_json.factorio_version.clone(),
+            dependencies: release.info_json.dependencies.clone(),
+        })
+    }
+}
+
+/// Collects info about latest releases of given mods from the cache.
+///
+/// Mods that couldn't be loaded are skipped, and their errors are returned
+/// alongside.
+pub fn latest_mods_info(
+    cache: &Cache,
+    mod_list: &[Mod],
+) -> (BTreeMap<String, ModWithInfo>, Vec<Error>) {
+    let mut mod_map = BTreeMap::new();
+    let mut errors = vec![];
+    for m in mod_list {
+        let name = &m.name;
+        let latest_version = m.latest_release.as_ref();
+        let mod_full = match cache.load_mod(name) {
+            Ok(mod_full) => mod_full,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+        if latest_version.is_some() == mod_full.releases.is_empty() {
+            errors.push(Error::Parse {
+                mod_name: name.clone(),
+                field: "releases".into(),
+                message: "latest release doesn't match full metadata".into(),
+            });
+        }
+        if let Some(release) = mod_full.releases.into_iter().find(|x| {
+            Some(x.version.as_str())
+                == latest_version.map(|x| x.version.as_str())
+        }) {
+            mod_map.insert(
+                name.clone(),
+                ModWithInfo {
+                    deprecated: mod_full.deprecated,
+                    factorio_version: release.info_json.factorio_version,
+                    dependencies: release.info_json.dependencies,
+                },
+            );
+        }
+    }
+    (mod_map, errors)
+}
+
+#[derive(Debug, Clone)]
+pub struct BrokenMod {
+    pub info: ModWithInfo,
+    /// deprecated, broken, or unavailable built-in dependencies
+    pub broken_deps: Vec<String>,
+}
+
+/// Result of [`find_broken_mods`].
+pub struct BrokenReport {
+    pub mods: BTreeMap<String, ModWithInfo>,
+    pub deprecated: BTreeSet<String>,
+    pub working: BTreeSet<String>,
+    pub broken: BTreeMap<String, BrokenMod>,
+    /// mods depending on a mod that doesn't exist, with that dependency
+    pub typod: BTreeMap<String, String>,
+    /// mods that couldn't be resolved, because of dependency cycles
+    pub unresolved: BTreeSet<String>,
+    /// working mods whose required dependencies include mutually incompatible
+    /// mods
+    pub uninstallable: BTreeMap<String, Vec<Conflict>>,
+}
+
+/// Maps every mod to mods whose latest release requires it.
+pub fn reverse_deps(
+    mods: &BTreeMap<String, ModWithInfo>,
+) -> BTreeMap<String, BTreeSet<String>> {
+    let mut rdeps = BTreeMap::<String, BTreeSet<String>>::new();
+    for (name, m) in mods {
+        for dep in &m.dependencies {
+            if matches!(dep.prefix, DepPrefix::Required) {
+                rdeps
+                    .entry(dep.name.clone())
+                    .or_default()
+                    .insert(name.clone());
+            }
+        }
+    }
+    rdeps
+}
+
+/// [`BrokenReport`] of latest releases of given mods, together with their
+/// reverse dependencies.
+pub struct Analysis {
+    pub report: BrokenReport,
+    /// mods requiring each mod
+    pub rdeps: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Analysis {
+    pub fn new<'a>(
+        mods: impl IntoIterator<Item = &'a ModFull>,
+        space_age: bool,
+    ) -> Self {
+        let mod_map = mods
+            .into_iter()
+            .filter_map(|m| Some((m.name.clone(), ModWithInfo::latest(m)?)))
+            .collect();
+        let rdeps = reverse_deps(&mod_map);
+        Self {
+            report: find_broken_mods(mod_map, space_age),
+            rdeps,
+        }
+    }
+}
+
+/// Status of a mod in [`BrokenReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ModStatus {
+    Working,
+    /// requires deprecated, broken, missing, or unavailable built-in mods
+    Broken,
+    Deprecated,
+    /// not on the mod portal
+    Missing,
+    /// working, but its required dependencies are incompatible with each
+    /// other
+    Incompatible,
+    BuiltIn,
+    /// part of a dependency cycle
+    Unresolved,
+}
+
+impl ModStatus {
+    pub const ALL: &[Self] = &[
+        Self::Working,
+        Self::Broken,
+        Self::Deprecated,
+        Self::Missing,
+        Self::Incompatible,
+        Self::BuiltIn,
+        Self::Unresolved,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Working => "working",
+            Self::Broken => "broken",
+            Self::Deprecated => "deprecated",
+            Self::Missing => "missing",
+            Self::Incompatible => "incompatible",
+            Self::BuiltIn => "built-in",
+            Self::Unresolved => "unresolved",
+        }
+    }
+}
+
+impl fmt::Display for ModStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Finds mods whose latest release can't be loaded, because of their
+/// dependencies being deprecated, broken, or not available.
+pub fn find_broken_mods(
+    mod_map: BTreeMap<String, ModWithInfo>,
+    space_age: bool,
+) -> BrokenReport {
+    let mut deprecated = BTreeSet::<String>::new();
+    let mut rest = BTreeMap::<&str, &ModWithInfo>::new();
+    for (name, m) in &mod_map {
+        if m.deprecated {
+            deprecated.insert(name.into());
+        } else {
+            rest.insert(name, m);
+        }
+    }
+    let mut deprecated_deps = deprecated.clone();
+    for &m in INTERNAL_MODS {
+        deprecated_deps.remove(m);
+    }
+    let deprecated_deps = deprecated_deps;
+
+    let mut broken = BTreeMap::new();
+    let mut working = BTreeSet::<String>::new();
+    let mut typod = BTreeMap::new();
+    while !rest.is_empty() {
+        let before = rest.len();
+        for (name, m) in mem::take(&mut rest) {
+            let internal = m
+                .factorio_version
+                .parse()
+                .map(|x| internal_mods(x, space_age))
+                .unwrap_or(&INTERNAL_MODS[..1]);
+            let iter = m
+                .dependencies
+                .iter()
+                .filter(|x| matches!(x.prefix, DepPrefix::Required));
+            let is_broken = |x: &&Dep| {
+                deprecated_deps.contains(&x.name)
+                    | broken.contains_key(&x.name)
+                    | (INTERNAL_MODS.contains(&x.name.as_str())
+                        & !internal.contains(&x.name.as_str()))
+            };
+            if iter.clone().all(|x| {
+                working.contains(&x.name) | internal.contains(&x.name.as_str())
+            }) {
+                working.insert(name.to_string());
+            } else if let Some(typod_dep) = iter.clone().find(|x| {
+                !mod_map.contains_key(&*x.name)
+                    && !INTERNAL_MODS.contains(&x.name.as_str())
+            }) {
+                typod.insert(name.to_string(), typod_dep.name.clone());
+            } else if iter.clone().any(|x| is_broken(&x)) {
+                let broken_deps = iter
+                    .filter(is_broken)
+                    .map(|x| x.name.clone())
+                    .collect::<Vec<_>>();
+                broken.insert(
+                    name.to_string(),
+                    BrokenMod {
+                        info: m.clone(),
+                        broken_deps,
+                    },
+                );
+            } else {
+                rest.insert(name, m);
+            }
+        }
+        // rest depends on each other
+        if rest.len() == before {
+            break;
+        }
+    }
+    let unresolved = rest.keys().map(|x| x.to_string()).collect();
+
+    let mut uninstallable = BTreeMap::new();
+    for name in &working {
+        let deps_of =
+            |name: &str| mod_map.get(name).map(|x| x.dependencies.as_slice());
+        let conflicts = find_conflicts(
+            &required_closure([name.as_str()], deps_of),
+            deps_of,
+        );
+        if !conflicts.is_empty() {
+            uninstallable.insert(name.clone(), conflicts);
+        }
+    }
+
+    BrokenReport {
+        mods: mod_map,
+        deprecated,
+        working,
+        broken,
+        typod,
+        unresolved,
+        uninstallable,
+    }
+}
+
+impl BrokenReport {
+    pub fn status(&self, name: &str) -> ModStatus {
+        if INTERNAL_MODS.contains(&name) {
+            ModStatus::BuiltIn
+        } else if self.deprecated.contains(name) {
+            ModStatus::Deprecated
+        } else if self.broken.contains_key(name)
+            || self.typod.contains_key(name)
+        {
+            ModStatus::Broken
+        } else if self.uninstallable.contains_key(name) {
+            ModStatus::Incompatible
+        } else if self.working.contains(name) {
+            ModStatus::Working
+        } else if self.unresolved.contains(name) {
+            ModStatus::Unresolved
+        } else {
+            ModStatus::Missing
+        }
+    }
+
+    /// Explains why a mod isn't working, one line per reason.
+    pub fn reasons(&self, name: &str) -> Vec<String> {
+        if let Some(m) = self.broken.get(name) {
+            return m
+                .broken_deps
+                .iter()
+                .map(|x| match self.status(x) {
+                    ModStatus::BuiltIn => format!(
+                        "requires {x}, which isn't available for factorio {}",
+                        m.info.factorio_version
+                    ),
+                    status => format!("requires {status} {x}"),
+                })
+                .collect();
+        }
+        if let Some(dep) = self.typod.get(name) {
+            return vec![format!("requires missing {dep}")];
+        }
+        if let Some(conflicts) = self.uninstallable.get(name) {
+            return conflicts.iter().map(|x| x.to_string()).collect();
+        }
+        if self.unresolved.contains(name) {
+            return vec!["its dependencies depend on each other".into()];
+        }
+        vec![]
+    }
+
+    /// Broken mods written into reports.
+    ///
+    /// Mods for 2.0 are skipped, unless they need missing built-in mods, as
+    /// those can't be fixed by updating their dependencies.
+    pub fn reported_broken(&self) -> impl Iterator<Item = (&str, &BrokenMod)> {
+        self.broken
+            .iter()
+            .filter(|(_, m)| {
+                // compared as versions, as strings would put "10.0" before
+                // "2.0"
+                let for_2_0 = m
+                    .info
+                    .factorio_version
+                    .parse::<Version>()
+                    .is_ok_and(|x| x >= SPACE_AGE_VERSION);
+                !for_2_0
+                    || m.broken_deps
+                        .iter()
+                        .any(|x| INTERNAL_MODS.contains(&x.as_str()))
+            })
+            .map(|(name, m)| (name.as_str(), m))
+    }
+
+    /// Writes `deprecated.txt`, `uninstallable.txt`, and `broken*.txt` reports
+    /// into `dir`.
+    pub fn write_reports(&self, dir: &Path) -> Result<(), Error> {
+        let mut deprecated_file = File::create(dir.join("deprecated.txt"))?;
+        for name in &self.deprecated {
+            writeln!(deprecated_file, "{name}",)?;
+        }
+
+        let mut u_file = File::create(dir.join("uninstallable.txt"))?;
+        for (name, conflicts) in &self.uninstallable {
+            writeln!(
+                u_file,
+                "{name} for {} because of:",
+                self.mods[name].factorio_version
+            )?;
+            for conflict in conflicts {
+                writeln!(u_file, "  {conflict}")?;
+            }
+        }
+
+        let broken = || self.reported_broken();
+        let broken_for_1_1 = || {
+            broken().filter(|(_, m)| {
+                m.info.factorio_version.parse() == Ok(Version::new(1, 1, 0))
+            })
+        };
+        let mut b_file = File::create(dir.join("broken.txt"))?;
+        for (name, m) in broken() {
+            writeln!(b_file, "{name} for {}", m.info.factorio_version)?;
+        }
+
+        let mut bwr_file = File::create(dir.join("broken_with_reason.txt"))?;
+        for (name, m) in broken() {
+            writeln!(
+                bwr_file,
+                "{name} for {} because of:",
+                m.info.factorio_version
+            )?;
+            for broken_dep in &m.broken_deps {
+                writeln!(bwr_file, "  {broken_dep}")?;
+            }
+        }
+
+        let mut b_file = File::create(dir.join("broken_for_1.1.txt"))?;
+        for (name, m) in broken_for_1_1() {
+            writeln!(b_file, "{name} for {}", m.info.factorio_version)?;
+        }
+
+        let mut bwrf1_1_file =
+            File::create(dir.join("broken_with_reason_for_1.1.txt"))?;
+        for (name, m) in broken_for_1_1() {
+            writeln!(
+                bwrf1_1_file,
+                "{name} for {} because of:",
+                m.info.factorio_version
+            )?;
+            for broken_dep in &m.broken_deps {
+                writeln!(bwrf1_1_file, "  {broken_dep}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+//! Choosing releases of mods, so that given mods can be loaded together.
+
+use core::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    INTERNAL_MODS,
+    analysis::conflicts::{Conflict, find_conflicts},
+    deserialization::{DepPrefix, FullInfoJson, Release},
+    internal_mods,
+    version::{Version, VersionReq},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    /// mod isn't known
+    Missing(String),
+    /// built-in mod isn't available in chosen factorio version
+    Unavailable(String),
+    /// no release of a mod is for chosen factorio version, and matches all
+    /// requirements of mods depending on it
+    NoRelease {
+        name: String,
+        requirements: Vec<VersionReq>,
+    },
+    /// chosen mods are incompatible with each other
+    Conflicts(Vec<Conflict>),
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolveError::Missing(name) => write!(f, "unknown mod {name}"),
+            ResolveError::Unavailable(name) => {
+                write!(f, "{name} isn't available in this factorio version")
+            }
+            ResolveError::NoRelease { name, requirements } => {
+                write!(f, "no release of {name} for this factorio version")?;
+                for (i, req) in requirements.iter().enumerate() {
+                    let sep = if i == 0 { " matches" } else { "," };
+                    write!(f, "{sep} {req}")?;
+                }
+                Ok(())
+            }
+            ResolveError::Conflicts(conflicts) => {
+                for (i, conflict) in conflicts.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { ", " };
+                    write!(f, "{sep}{conflict}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl core::error::Error for ResolveError {}
+
+/// Newest release for given factorio version, matching all requirements.
+fn pick<'a>(
+    releases: &'a [Release<FullInfoJson>],
+    factorio_version: Version,
+    requirements: &BTreeSet<VersionReq>,
+) -> Option<(Version, &'a Release<FullInfoJson>)> {
+    releases
+        .iter()
+        .filter_map(|x| {
+            let for_version = x.info_json.factorio_version.parse::<Version>();
+            (for_version.ok()?.factorio_version() == factorio_version)
+                .then_some((x.version.parse::<Version>().ok()?, x))
+        })
+        .filter(|(version, _)| requirements.iter().all(|x| x.matches(*version)))
+        .max_by_key(|(version, _)| *version)
+}
+
+/// Chooses releases of `roots` and every mod they require, for given factorio
+/// version.
+///
+/// Newest releases are preferred, and replaced by older ones only when
+/// requirements of other chosen releases rule them out. Requirements are
+/// never dropped, so this can fail where a different combination of older
+/// releases would work.
+///
+/// Built-in mods aren't included in the result.
+pub fn resolve<'a>(
+    roots: &[&str],
+    factorio_version: Version,
+    space_age: bool,
+    releases_of: impl Fn(&str) -> Option<&'a [Release<FullInfoJson>]>,
+) -> Result<BTreeMap<String, &'a Release<FullInfoJson>>, ResolveError> {
+    let factorio_version = factorio_version.factorio_version();
+    let internal = internal_mods(factorio_version, space_age);
+    let mut requirements = BTreeMap::<String, BTreeSet<VersionReq>>::new();
+    loop {
+        let mut chosen = BTreeMap::new();
+        let mut new_requirements = requirements.clone();
+        let mut queue: Vec<String> =
+            roots.iter().map(|x| x.to_string()).collect();
+        while let Some(name) = queue.pop() {
+            if chosen.contains_key(&name) {
+                continue;
+            }
+            if INTERNAL_MODS.contains(&name.as_str()) {
+                if !internal.contains(&name.as_str()) {
+                    return Err(ResolveError::Unavailable(name));
+                }
+                continue;
+            }
+            let releases = releases_of(&name)
+                .ok_or_else(|| ResolveError::Missing(name.clone()))?;
+            let reqs = requirements.get(&name).cloned().unwrap_or_default();
+            let Some((_, release)) = pick(releases, factorio_version, &reqs)
+            else {
+                return Err(ResolveError::NoRelease {
+                    name,
+                    requirements: reqs.into_iter().collect(),
+                });
+            };
+            for dep in &release.info_json.dependencies {
+                if !matches!(dep.prefix, DepPrefix::Required) {
+                    continue;
+                }
+                if let Some(req) = dep.version {
+                    new_requirements
+                        .entry(dep.name.clone())
+                        .or_default()
+                        .insert(req);
+                }
+                queue.push(dep.name.clone());
+            }
+            chosen.insert(name, release);
+        }
+        // requirements only grow, so this ends
+        if new_requirements == requirements {
+            let deps_of = |name: &str| {
+                chosen
+                    .get(name)
+                    .map(|x| x.info_json.dependencies.as_slice())
+            };
+            let names = chosen.keys().map(|x| x.as_str()).collect();
+            let conflicts = find_conflicts(&names, deps_of);
+            if !conflicts.is_empty() {
+                return Err(ResolveError::Conflicts(conflicts));
+            }
+            return Ok(chosen);
+        }
+        requirements = new_requirements;
+    }
+}
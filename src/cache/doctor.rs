@@ -0,0 +1,209 @@
+//! Checks of cached metadata against format expected from current portal data.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    Error,
+    cache::{Cache, from_slice_with_path, parse_mod},
+    deserialization::{Category, Dep, DepPrefix, ModFull, ParseDepError},
+};
+
+// models in the format portal uses for current mods, with nothing optional
+// that should be there. they are only parsed to check the format, so fields
+// are never read
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct StrictModFull {
+    category: String,
+    changelog: Option<String>,
+    created_at: String,
+    downloads_count: u64,
+    #[serde(default)]
+    deprecated: bool,
+    description: Option<String>,
+    homepage: String,
+    images: Vec<StrictImage>,
+    license: Option<StrictLicense>,
+    name: String,
+    owner: String,
+    releases: Vec<StrictRelease>,
+    #[serde(default)]
+    score: f32,
+    source_url: Option<String>,
+    summary: String,
+    tags: Option<Vec<String>>,
+    thumbnail: Option<String>,
+    title: String,
+    updated_at: String,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct StrictImage {
+    id: String,
+    thumbnail: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct StrictLicense {
+    description: String,
+    id: String,
+    name: String,
+    title: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct StrictRelease {
+    download_url: String,
+    file_name: String,
+    info_json: StrictInfoJson,
+    released_at: String,
+    sha1: String,
+    version: String,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct StrictInfoJson {
+    dependencies: StrictDeps,
+    factorio_version: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum StrictDeps {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Problems found with a single cached mod.
+pub struct Diagnosis {
+    pub name: String,
+    /// why the file fails strict parsing
+    pub strict_error: Option<String>,
+    /// why the file can't be parsed at all, making the mod unusable
+    pub error: Option<Error>,
+    pub unknown_category: Option<String>,
+    /// fields not covered by the model, of the mod and its releases
+    pub unknown_fields: BTreeSet<String>,
+}
+
+impl Diagnosis {
+    pub fn is_ok(&self) -> bool {
+        self.strict_error.is_none()
+            && self.error.is_none()
+            && self.unknown_category.is_none()
+            && self.unknown_fields.is_empty()
+    }
+}
+
+fn diagnose_mod(cache: &Cache, name: &str) -> Result<Diagnosis, Error> {
+    let path = cache.mod_path(name);
+    let bytes = fs::read(&path)?;
+    let strict_error =
+        from_slice_with_path::<StrictModFull>(&mut bytes.clone())
+            .err()
+            .map(|(field, message)| match field.is_empty() {
+                true => message,
+                false => format!("at `{field}`: {message}"),
+            });
+    let mut diagnosis = Diagnosis {
+        name: name.into(),
+        strict_error,
+        error: None,
+        unknown_category: None,
+        unknown_fields: BTreeSet::new(),
+    };
+    match parse_mod(name, &path, &mut bytes.clone()) {
+        Ok(m) => {
+            if let Category::Unknown(x) = m.category {
+                diagnosis.unknown_category = Some(x);
+            }
+            diagnosis.unknown_fields.extend(m.extra.into_keys());
+            for release in m.releases {
+                diagnosis.unknown_fields.extend(
+                    release
+                        .extra
+                        .into_keys()
+                        .map(|x| format!("releases[].{x}")),
+                );
+            }
+        }
+        Err(e) => diagnosis.error = Some(e),
+    }
+    Ok(diagnosis)
+}
+
+/// Checks every cached mod, returning ones with any problems.
+pub fn diagnose(cache: &Cache) -> Result<Vec<Diagnosis>, Error> {
+    let mut diagnoses = vec![];
+    for name in cache.cached_mod_names()? {
+        let diagnosis = diagnose_mod(cache, &name)?;
+        if !diagnosis.is_ok() {
+            diagnoses.push(diagnosis);
+        }
+    }
+    Ok(diagnoses)
+}
+
+/// Dependency string that new and legacy dependency parsers disagree on.
+pub struct DepDisagreement {
+    pub original: String,
+    /// mods with this dependency string
+    pub mods: BTreeSet<String>,
+    pub parsed: Result<Dep, ParseDepError>,
+    pub legacy: (DepPrefix, String, String),
+}
+
+/// Parses every distinct dependency string of cached mods with both
+/// [`Dep::parse`] and [`Dep::parse_legacy`].
+///
+/// Returns number of distinct strings, and ones where results differ.
+pub fn compare_dep_parsers(mods: &[ModFull]) -> (usize, Vec<DepDisagreement>) {
+    let mut strings = BTreeMap::<&str, BTreeSet<String>>::new();
+    for m in mods {
+        for release in &m.releases {
+            for dep in &release.info_json.dependencies {
+                strings
+                    .entry(&dep.original)
+                    .or_default()
+                    .insert(m.name.clone());
+            }
+        }
+    }
+
+    let mut disagreements = vec![];
+    for (&original, mods) in &strings {
+        let parsed = Dep::parse(original);
+        let legacy = Dep::parse_legacy(original);
+        let agrees = parsed.as_ref().is_ok_and(|dep| {
+            let (prefix, name, version) = &legacy;
+            dep.prefix == *prefix
+                && dep.name == *name
+                && match dep.version {
+                    Some(req) => version.parse() == Ok(req),
+                    None => version.is_empty(),
+                }
+        });
+        if !agrees {
+            disagreements.push(DepDisagreement {
+                original: original.to_string(),
+                mods: mods.clone(),
+                parsed,
+                legacy,
+            });
+        }
+    }
+    (strings.len(), disagreements)
+}
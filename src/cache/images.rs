@@ -0,0 +1,183 @@
+//! On-disk cache of mod thumbnails and screenshots.
+//!
+//! Images are stored in `images` folder of the cache, one file per url.
+//! Once they take more than size limit, least recently used ones are removed.
+
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    time::SystemTime,
+};
+
+use crate::Error;
+
+/// Host of images uploaded to the mod portal.
+pub const ASSETS_HOST: &str = "https://assets-mod.factorio.com";
+pub const DEFAULT_MAX_SIZE: u64 = 512 * 1024 * 1024;
+
+pub struct ImageCache {
+    dir: PathBuf,
+    max_size: u64,
+    /// total size of cached images, kept up to date by [`ImageCache::insert`]
+    size: AtomicU64,
+}
+
+/// Inserts started by this process, so each writes its own temporary file.
+static INSERTS: AtomicU64 = AtomicU64::new(0);
+
+/// Turns url into a file name, keeping the host, so images from other hosts
+/// can't collide.
+///
+/// Bytes other than letters, digits, `.` and `-` are escaped as `_` and their
+/// hex value, `_` included, so different urls never share a file.
+fn file_name(url: &str) -> String {
+    let url = url.split_once("://").map_or(url, |(_, x)| x);
+    let mut name = String::with_capacity(url.len());
+    for x in url.bytes() {
+        match x {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' => {
+                name.push(x as char)
+            }
+            _ => {
+                let _ = write!(name, "_{x:02x}");
+            }
+        }
+    }
+    name
+}
+
+/// Makes urls relative to the mod portal's assets absolute.
+pub fn asset_url(url: &str) -> String {
+    if url.starts_with('/') {
+        format!("{ASSETS_HOST}{url}")
+    } else {
+        url.into()
+    }
+}
+
+fn dir_size(dir: &Path) -> Result<u64, Error> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        size += entry?.metadata()?.len();
+    }
+    Ok(size)
+}
+
+impl ImageCache {
+    /// Opens image cache at given directory, creating it if needed.
+    pub fn open(dir: impl Into<PathBuf>, max_size: u64) -> Result<Self, Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let size = dir_size(&dir)?;
+        Ok(Self {
+            dir,
+            max_size,
+            size: AtomicU64::new(size),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    pub fn path(&self, url: &str) -> PathBuf {
+        self.dir.join(file_name(url))
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        self.path(url).is_file()
+    }
+
+    /// Reads cached image, marking it as recently used.
+    pub fn get(&self, url: &str) -> Option<Vec<u8>> {
+        let path = self.path(url);
+        let bytes = fs::read(&path).ok()?;
+        // failing to update it only makes eviction less accurate
+        let _ = fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|x| x.set_modified(SystemTime::now()));
+        Some(bytes)
+    }
+
+    /// Saves an image, evicting old ones if cache got too big.
+    pub fn insert(&self, url: &str, bytes: &[u8]) -> Result<(), Error> {
+        let path = self.path(url);
+        // written under temporary name, so other readers never see partial
+        // file, which is unique so concurrent inserts of the same url don't
+        // write into the same one
+        let id = INSERTS.fetch_add(1, Ordering::Relaxed);
+        let tmp = self.dir.join(format!(
+            "{}.{}-{id}.tmp",
+            file_name(url),
+            process::id()
+        ));
+        fs::write(&tmp, bytes)?;
+        let old_size = fs::metadata(&path).map_or(0, |x| x.len());
+        fs::rename(&tmp, &path)?;
+        let added = (bytes.len() as u64).saturating_sub(old_size);
+        let size = self.size.fetch_add(added, Ordering::Relaxed) + added;
+        if size > self.max_size {
+            self.evict()?;
+        }
+        Ok(())
+    }
+
+    /// Removes least recently used images, until cache takes at most 90% of
+    /// its size limit, so that evicting doesn't happen on every insert.
+    pub fn evict(&self) -> Result<(), Error> {
+        let mut files = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((used, metadata.len(), entry.path()));
+        }
+        files.sort();
+        let mut size = files.iter().map(|(_, len, _)| len).sum::<u64>();
+        let target = self.max_size / 10 * 9;
+        for (_, len, path) in files {
+            if size <= target {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => size -= len,
+                // removed by someone else
+                Err(e) if e.kind() == io::ErrorKind::NotFound => size -= len,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.size.store(size, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_dont_collide() {
+        assert_eq!(
+            file_name("https://assets-mod.factorio.com/a/b.png"),
+            "assets-mod.factorio.com_2fa_2fb.png"
+        );
+        assert_ne!(
+            file_name("https://host/a_b/c"),
+            file_name("https://host/a/b_c")
+        );
+        assert_ne!(
+            file_name("https://host/a b"),
+            file_name("https://host/a_b")
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! On-disk cache of mod portal metadata.
+//!
+//! Cache consists of `mods.json`, holding short info about every mod, and
+//! `mods/{name}.json` files with full metadata of each mod.
+
+pub mod doctor;
+pub mod images;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    APP_ID, Error,
+    cache::images::{DEFAULT_MAX_SIZE, ImageCache},
+    deserialization::{Mod, ModFull, ModList},
+    download::{Progress, download_mod_list, download_mods_meta_full},
+    search::SearchIndex,
+};
+
+#[derive(Clone)]
+pub struct Cache {
+    root: PathBuf,
+}
+
+impl Cache {
+    /// Opens cache in app's storage directory.
+    pub fn open_default() -> Result<Self, Error> {
+        let root = eframe::storage_dir(APP_ID).ok_or(Error::NoStorageDir)?;
+        Self::open(root)
+    }
+
+    /// Opens cache at given directory, creating it if needed.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, Error> {
+        let cache = Self { root: root.into() };
+        fs::create_dir_all(cache.mods_dir())?;
+        Ok(cache)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Opens image cache inside this cache, with default size limit.
+    pub fn images(&self) -> Result<ImageCache, Error> {
+        ImageCache::open(self.images_dir(), DEFAULT_MAX_SIZE)
+    }
+
+    pub fn images_dir(&self) -> PathBuf {
+        self.root.join("images")
+    }
+
+    pub fn mods_dir(&self) -> PathBuf {
+        self.root.join("mods")
+    }
+
+    /// Downloaded mod files, shared by crater runs.
+    pub fn zips_dir(&self) -> PathBuf {
+        self.root.join("zips")
+    }
+
+    pub fn mod_list_path(&self) -> PathBuf {
+        self.root.join("mods.json")
+    }
+
+    pub fn mod_path(&self, name: &str) -> PathBuf {
+        self.mods_dir().join(format!("{name}.json"))
+    }
+
+    /// Loads short info about all mods, or empty list if it wasn't downloaded
+    /// yet.
+    pub fn load_short_mod_list(&self) -> Result<Vec<Mod>, Error> {
+        let path = self.mod_list_path();
+        let Ok(mut bytes) = fs::read(&path) else {
+            return Ok(vec![]);
+        };
+        Ok(parse_mod_list(&path, &mut bytes)?.results)
+    }
+
+    /// Names of all mods with cached full metadata.
+    pub fn cached_mod_names(&self) -> Result<BTreeSet<String>, Error> {
+        let mut names = BTreeSet::new();
+        for entry in fs::read_dir(self.mods_dir())? {
+            let file_name = entry?.file_name();
+            let Some(name) =
+                file_name.to_str().and_then(|x| x.strip_suffix(".json"))
+            else {
+                continue;
+            };
+            names.insert(name.to_string());
+        }
+        Ok(names)
+    }
+
+    /// Loads full metadata of a single mod.
+    pub fn load_mod(&self, name: &str) -> Result<ModFull, Error> {
+        let path = self.mod_path(name);
+        let mut bytes = fs::read(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::NotCached(name.into()),
+            _ => Error::Io(e),
+        })?;
+        parse_mod(name, &path, &mut bytes)
+    }
+
+    /// Loads full metadata of all cached mods.
+    ///
+    /// Mods that couldn't be loaded are skipped, and their errors are returned
+    /// alongside.
+    pub fn load_mod_list(&self) -> Result<(Vec<ModFull>, Vec<Error>), Error> {
+        let mut mod_list = Vec::new();
+        let mut errors = Vec::new();
+        for name in self.cached_mod_names()? {
+            match self.load_mod(&name) {
+                Ok(mod_full) => mod_list.push(mod_full),
+                Err(e) => errors.push(e),
+            }
+        }
+        Ok((mod_list, errors))
+    }
+
+    /// Downloads current mod list from the portal, and full metadata of mods
+    /// that changed since last update (or of all of them, with `all` set).
+    ///
+    /// Returns the new mod list, and errors of mods whose metadata couldn't be
+    /// downloaded.
+    ///
+    /// Saved search index is updated as well, and its error is returned with
+    /// the others.
+    pub fn update(&self, all: bool) -> Result<(Vec<Mod>, Vec<Error>), Error> {
+        self.update_with_progress(all, &Progress::default())
+    }
+
+    /// [`Cache::update`], reporting progress of downloading metadata into
+    /// `progress`.
+    pub fn update_with_progress(
+        &self,
+        all: bool,
+        progress: &Progress,
+    ) -> Result<(Vec<Mod>, Vec<Error>), Error> {
+        let mod_json_list = self.cached_mod_names()?;
+        let old_mod_list: BTreeMap<String, Option<String>> = self
+            .load_short_mod_list()?
+            .into_iter()
+            .map(|x| (x.name, x.latest_release.map(|x| x.sha1)))
+            .collect();
+
+        let new_mod_list = download_mod_list()?;
+        let path = self.mod_list_path();
+        fs::write(&path, &new_mod_list)?;
+        let new_mod_list =
+            parse_mod_list(&path, &mut new_mod_list.into_bytes())?.results;
+        let mut errors = if !all {
+            let updated_mod_list = new_mod_list
+                .iter()
+                .map(|x| {
+                    (
+                        x.name.as_str(),
+                        x.latest_release.as_ref().map(|x| x.sha1.as_str()),
+                    )
+                })
+                .filter(|&(name, sha1)| {
+                    old_mod_list
+                        .get(name)
+                        .and_then(|x| x.as_deref())
+                        .zip(sha1)
+                        .map(|(a, b)| a != b)
+                        .unwrap_or(true)
+                        | !mod_json_list.contains(name)
+                })
+                .map(|(name, _)| name);
+            download_mods_meta_full(
+                &self.mods_dir(),
+                updated_mod_list,
+                progress,
+            )?
+        } else {
+            download_mods_meta_full(
+                &self.mods_dir(),
+                new_mod_list.iter().map(|x| x.name.as_str()),
+                progress,
+            )?
+        };
+        // keep search index in sync, if it was ever built; mods that failed
+        // to load are already reported by whatever loads them later, and
+        // failing index doesn't undo downloaded metadata
+        if SearchIndex::exists(self)
+            && let Err(e) = SearchIndex::open(self)
+        {
+            errors.push(e);
+        }
+        Ok((new_mod_list, errors))
+    }
+}
+
+/// Deserializes json, returning path of the field that failed to parse along
+/// with the error.
+fn from_slice_with_path<T: DeserializeOwned>(
+    bytes: &mut [u8],
+) -> Result<T, (String, String)> {
+    let mut des = simd_json::Deserializer::from_slice(bytes)
+        .map_err(|e| (String::new(), e.to_string()))?;
+    serde_path_to_error::deserialize(&mut des)
+        .map_err(|e| (e.path().to_string(), e.inner().to_string()))
+}
+
+fn parse_mod_list(path: &Path, bytes: &mut [u8]) -> Result<ModList, Error> {
+    from_slice_with_path(bytes).map_err(|(field, message)| {
+        Error::CacheCorrupt {
+            path: path.into(),
+            message: format!("at `{field}`: {message}"),
+        }
+    })
+}
+
+fn parse_mod(
+    name: &str,
+    path: &Path,
+    bytes: &mut [u8],
+) -> Result<ModFull, Error> {
+    from_slice_with_path(bytes).map_err(|(field, message)| {
+        // not even valid json
+        if field.is_empty() {
+            Error::CacheCorrupt {
+                path: path.into(),
+                message,
+            }
+        } else {
+            Error::Parse {
+                mod_name: name.into(),
+                field,
+                message,
+            }
+        }
+    })
+}
@@ -0,0 +1,393 @@
+//! Parser of factorio's `changelog.txt` format.
+//!
+//! ```text
+//! ---------------------------------------------------------------------------------------------------
+//! Version: 1.2.3
+//! Date: 2024-10-21
+//!   Bugfixes:
+//!     - Fixed a crash.
+//!       Second line of the same entry.
+//! ```
+
+use core::{fmt, str::FromStr};
+
+use crate::version::Version;
+
+/// Line starting every version section.
+const SEPARATOR: &str = "---------------------------------------------------------------------------------------------------";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changelog {
+    /// in order they appear in, which is usually newest first
+    pub versions: Vec<ChangelogVersion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangelogVersion {
+    pub version: Version,
+    pub date: Option<String>,
+    pub categories: Vec<ChangelogCategory>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangelogCategory {
+    pub name: String,
+    /// multiline entries have their lines joined with `\n`
+    pub entries: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangelogErrorKind {
+    Tab,
+    /// Line of dashes that isn't exactly as long as the separator.
+    InvalidSeparator,
+    /// Content before the first separator.
+    MissingSeparator,
+    /// Separator isn't followed by `Version: ` line.
+    MissingVersion,
+    InvalidVersion(String),
+    DuplicateVersion(Version),
+    DuplicateCategory(String),
+    /// Entry before any category.
+    EntryOutsideCategory,
+    /// Continuation line that doesn't follow an entry.
+    ContinuationOutsideEntry,
+    EmptyEntry,
+    /// Line that doesn't match any part of the format.
+    InvalidLine,
+}
+
+/// Reason why factorio would reject a changelog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangelogError {
+    /// 1-based
+    pub line: usize,
+    pub kind: ChangelogErrorKind,
+}
+
+impl fmt::Display for ChangelogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ChangelogErrorKind::Tab => write!(f, "tabs aren't allowed"),
+            ChangelogErrorKind::InvalidSeparator => {
+                write!(f, "separator must be exactly 99 dashes")
+            }
+            ChangelogErrorKind::MissingSeparator => {
+                write!(f, "expected separator before first version")
+            }
+            ChangelogErrorKind::MissingVersion => {
+                write!(f, "expected `Version: ` after separator")
+            }
+            ChangelogErrorKind::InvalidVersion(v) => {
+                write!(f, "invalid version {v:?}")
+            }
+            ChangelogErrorKind::DuplicateVersion(v) => {
+                write!(f, "version {v} is listed more than once")
+            }
+            ChangelogErrorKind::DuplicateCategory(c) => {
+                write!(f, "category {c:?} is listed more than once")
+            }
+            ChangelogErrorKind::EntryOutsideCategory => {
+                write!(f, "entry must be inside a category")
+            }
+            ChangelogErrorKind::ContinuationOutsideEntry => {
+                write!(f, "continuation line must follow an entry")
+            }
+            ChangelogErrorKind::EmptyEntry => write!(f, "entry is empty"),
+            ChangelogErrorKind::InvalidLine => write!(
+                f,
+                "expected separator, category (2 spaces), entry (4 spaces \
+                and `- `), or continuation (6 spaces)"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for ChangelogError {}
+
+impl Changelog {
+    /// Parses changelog, rejecting it the same way factorio would.
+    pub fn parse(s: &str) -> Result<Self, ChangelogError> {
+        let mut versions = Vec::<ChangelogVersion>::new();
+        let mut lines = s.lines().enumerate().peekable();
+        while let Some((i, line)) = lines.next() {
+            let err = |kind| ChangelogError { line: i + 1, kind };
+            if line.contains('\t') {
+                return Err(err(ChangelogErrorKind::Tab));
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line.starts_with('-')
+                && line.trim_end().bytes().all(|x| x == b'-')
+            {
+                if line.trim_end() != SEPARATOR {
+                    return Err(err(ChangelogErrorKind::InvalidSeparator));
+                }
+                let (i, line) = lines.next().unwrap_or((i + 1, ""));
+                let err = |kind| ChangelogError { line: i + 1, kind };
+                let version = line
+                    .strip_prefix("Version: ")
+                    .ok_or(err(ChangelogErrorKind::MissingVersion))?
+                    .trim();
+                let version = version.parse::<Version>().map_err(|_| {
+                    err(ChangelogErrorKind::InvalidVersion(version.into()))
+                })?;
+                if versions.iter().any(|x| x.version == version) {
+                    return Err(err(ChangelogErrorKind::DuplicateVersion(
+                        version,
+                    )));
+                }
+                let date = lines
+                    .next_if(|(_, x)| x.starts_with("Date: "))
+                    .map(|(_, x)| x["Date: ".len()..].trim().to_string());
+                versions.push(ChangelogVersion {
+                    version,
+                    date,
+                    categories: vec![],
+                });
+                continue;
+            }
+            let Some(current) = versions.last_mut() else {
+                return Err(err(ChangelogErrorKind::MissingSeparator));
+            };
+            let categories = &mut current.categories;
+            if let Some(rest) = line.strip_prefix("      ") {
+                let entry = categories
+                    .last_mut()
+                    .and_then(|x| x.entries.last_mut())
+                    .ok_or(err(ChangelogErrorKind::ContinuationOutsideEntry))?;
+                entry.push('\n');
+                entry.push_str(rest.trim());
+            } else if let Some(rest) = line.strip_prefix("    ") {
+                let entry = rest
+                    .strip_prefix("- ")
+                    .or_else(|| (rest.trim_end() == "-").then_some(""))
+                    .ok_or(err(ChangelogErrorKind::InvalidLine))?
+                    .trim();
+                if entry.is_empty() {
+                    return Err(err(ChangelogErrorKind::EmptyEntry));
+                }
+                categories
+                    .last_mut()
+                    .ok_or(err(ChangelogErrorKind::EntryOutsideCategory))?
+                    .entries
+                    .push(entry.into());
+            } else if let Some(rest) = line.strip_prefix("  ") {
+                let name = rest
+                    .trim_end()
+                    .strip_suffix(':')
+                    .filter(|x| !x.is_empty() && !x.starts_with(' '))
+                    .ok_or(err(ChangelogErrorKind::InvalidLine))?;
+                if categories.iter().any(|x| x.name == name) {
+                    return Err(err(ChangelogErrorKind::DuplicateCategory(
+                        name.into(),
+                    )));
+                }
+                categories.push(ChangelogCategory {
+                    name: name.into(),
+                    entries: vec![],
+                });
+            } else {
+                return Err(err(ChangelogErrorKind::InvalidLine));
+            }
+        }
+        Ok(Self { versions })
+    }
+
+    /// Versions newer than `version`.
+    pub fn since(
+        &self,
+        version: Version,
+    ) -> impl Iterator<Item = &ChangelogVersion> {
+        self.versions.iter().filter(move |x| x.version > version)
+    }
+}
+
+impl FromStr for Changelog {
+    type Err = ChangelogError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for ChangelogVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{SEPARATOR}")?;
+        writeln!(f, "Version: {}", self.version)?;
+        if let Some(date) = &self.date {
+            writeln!(f, "Date: {date}")?;
+        }
+        for category in &self.categories {
+            writeln!(f, "  {}:", category.name)?;
+            for entry in &category.entries {
+                writeln!(f, "    - {}", entry.replace('\n', "\n      "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes changelog back in factorio's format.
+impl fmt::Display for Changelog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for version in &self.versions {
+            write!(f, "{version}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Changelog with given sections, each starting with the separator.
+    fn sections(sections: &[&str]) -> String {
+        sections
+            .iter()
+            .map(|x| format!("{SEPARATOR}\n{x}"))
+            .collect()
+    }
+
+    fn error_of(s: &str) -> ChangelogError {
+        Changelog::parse(s).unwrap_err()
+    }
+
+    #[test]
+    fn multiple_versions() {
+        let text = sections(&[
+            "Version: 1.1.0\nDate: 2024-10-21\n  Features:\n    - Added a \
+            thing.\n  Bugfixes:\n    - Fixed a crash.\n",
+            "Version: 1.0.0\n  Features:\n    - First release.\n",
+        ]);
+        let changelog = Changelog::parse(&text).unwrap();
+        assert_eq!(changelog.versions.len(), 2);
+        let newest = &changelog.versions[0];
+        assert_eq!(newest.version, Version::new(1, 1, 0));
+        assert_eq!(newest.date.as_deref(), Some("2024-10-21"));
+        let names = newest.categories.iter().map(|x| x.name.as_str());
+        assert_eq!(names.collect::<Vec<_>>(), ["Features", "Bugfixes"]);
+        assert_eq!(newest.categories[1].entries, ["Fixed a crash."]);
+        assert_eq!(changelog.versions[1].date, None);
+        // writing it back gives the same text
+        assert_eq!(changelog.to_string(), text);
+    }
+
+    #[test]
+    fn continuation_lines() {
+        let text = sections(&[
+            "Version: 1.0.0\n  Bugfixes:\n    - Fixed a crash\n      when \
+            loading.\n      And another line.\n    - Second entry.\n",
+        ]);
+        let changelog = Changelog::parse(&text).unwrap();
+        let entries = &changelog.versions[0].categories[0].entries;
+        assert_eq!(
+            entries,
+            &[
+                "Fixed a crash\nwhen loading.\nAnd another line.",
+                "Second entry."
+            ]
+        );
+
+        let text = sections(&["Version: 1.0.0\n  Bugfixes:\n      loose\n"]);
+        assert_eq!(
+            error_of(&text),
+            ChangelogError {
+                line: 4,
+                kind: ChangelogErrorKind::ContinuationOutsideEntry,
+            }
+        );
+    }
+
+    #[test]
+    fn wrong_separator_length() {
+        let short = &SEPARATOR[1..];
+        let text = format!("{short}\nVersion: 1.0.0\n");
+        assert_eq!(
+            error_of(&text),
+            ChangelogError {
+                line: 1,
+                kind: ChangelogErrorKind::InvalidSeparator,
+            }
+        );
+        let text = format!("{SEPARATOR}-\nVersion: 1.0.0\n");
+        assert_eq!(error_of(&text).kind, ChangelogErrorKind::InvalidSeparator);
+    }
+
+    #[test]
+    fn tab_indentation() {
+        let text = sections(&["Version: 1.0.0\n  Features:\n\t- Tabbed.\n"]);
+        assert_eq!(
+            error_of(&text),
+            ChangelogError {
+                line: 4,
+                kind: ChangelogErrorKind::Tab,
+            }
+        );
+    }
+
+    #[test]
+    fn duplicate_version() {
+        let text = sections(&[
+            "Version: 1.0.0\n  Features:\n    - One.\n",
+            "Version: 1.0\n  Features:\n    - Two.\n",
+        ]);
+        assert_eq!(
+            error_of(&text),
+            ChangelogError {
+                line: 6,
+                kind: ChangelogErrorKind::DuplicateVersion(Version::new(
+                    1, 0, 0
+                )),
+            }
+        );
+    }
+
+    #[test]
+    fn duplicate_category() {
+        let text = sections(&[
+            "Version: 1.0.0\n  Features:\n    - One.\n  Features:\n    - \
+            Two.\n",
+        ]);
+        assert_eq!(
+            error_of(&text),
+            ChangelogError {
+                line: 5,
+                kind: ChangelogErrorKind::DuplicateCategory("Features".into()),
+            }
+        );
+        // the same category in another version is fine
+        let text = sections(&[
+            "Version: 1.0.1\n  Features:\n    - One.\n",
+            "Version: 1.0.0\n  Features:\n    - Two.\n",
+        ]);
+        assert!(Changelog::parse(&text).is_ok());
+    }
+
+    #[test]
+    fn since_boundaries() {
+        let text = sections(&[
+            "Version: 1.2.0\n",
+            "Version: 1.1.0\n",
+            "Version: 1.0.0\n",
+        ]);
+        let changelog = Changelog::parse(&text).unwrap();
+        let since = |major, minor, sub| {
+            changelog
+                .since(Version::new(major, minor, sub))
+                .map(|x| x.version)
+                .collect::<Vec<_>>()
+        };
+        // the version itself is excluded
+        assert_eq!(since(1, 1, 0), [Version::new(1, 2, 0)]);
+        assert_eq!(since(1, 2, 0), []);
+        assert_eq!(since(2, 0, 0), []);
+        assert_eq!(since(0, 0, 0).len(), 3);
+        assert_eq!(
+            since(1, 0, 5),
+            [Version::new(1, 2, 0), Version::new(1, 1, 0)]
+        );
+    }
+}
@@ -1,9 +1,16 @@
-use core::{fmt, str::FromStr};
+use core::{convert::Infallible, fmt, str::FromStr};
+use std::collections::BTreeMap;
 
 use serde::{
-    de::{self, Visitor},
     Deserialize, Deserializer,
+    de::{self, Visitor},
 };
+use simd_json::OwnedValue;
+
+use crate::version::{Version, VersionOp, VersionReq};
+
+/// Fields not covered by the model, kept as they were.
+pub type Extra = BTreeMap<String, OwnedValue>;
 
 #[derive(Deserialize)]
 pub struct ModList {
@@ -18,69 +25,223 @@ pub struct Mod {
     pub latest_release: Option<LatestRelease>,
 }
 
+// metadata of old mods doesn't always follow current format, so everything
+// that isn't needed to identify a mod is optional
 #[derive(Debug, Clone, Deserialize)]
 pub struct ModFull {
-    pub category: String,
+    #[serde(default, deserialize_with = "nullable")]
+    pub category: Category,
     pub changelog: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
     pub created_at: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub downloads_count: u64,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "nullable")]
     pub deprecated: bool,
     pub description: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
     pub homepage: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub images: Vec<Image>,
     pub license: Option<License>,
     pub name: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub owner: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub releases: Vec<Release<FullInfoJson>>,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "nullable")]
     pub score: f32,
     pub source_url: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
     pub summary: String,
     pub tags: Option<Vec<String>>,
     pub thumbnail: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
     pub title: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub updated_at: String,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl ModFull {
+    /// Returns release with highest version.
+    ///
+    /// Releases with unparsable versions are only used if there are no
+    /// others.
+    pub fn latest_release(&self) -> Option<&Release<FullInfoJson>> {
+        self.releases
+            .iter()
+            .filter_map(|x| Some((x.version.parse::<Version>().ok()?, x)))
+            .max_by_key(|&(version, _)| version)
+            .map(|(_, x)| x)
+            .or(self.releases.last())
+    }
+}
+
+/// Mod portal category, with `Unknown` for ones added after this was written.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    #[default]
+    NoCategory,
+    Content,
+    Overhaul,
+    Tweaks,
+    Utilities,
+    Scenarios,
+    ModPacks,
+    Localizations,
+    Internal,
+    Unknown(String),
+}
+
+impl Category {
+    pub const KNOWN: &[Category] = &[
+        Category::NoCategory,
+        Category::Content,
+        Category::Overhaul,
+        Category::Tweaks,
+        Category::Utilities,
+        Category::Scenarios,
+        Category::ModPacks,
+        Category::Localizations,
+        Category::Internal,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Category::NoCategory => "no-category",
+            Category::Content => "content",
+            Category::Overhaul => "overhaul",
+            Category::Tweaks => "tweaks",
+            Category::Utilities => "utilities",
+            Category::Scenarios => "scenarios",
+            Category::ModPacks => "mod-packs",
+            Category::Localizations => "localizations",
+            Category::Internal => "internal",
+            Category::Unknown(x) => x,
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Category {
+    type Err = Infallible;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Category::KNOWN
+            .iter()
+            .find(|x| x.as_str() == s)
+            .cloned()
+            .unwrap_or_else(|| match s {
+                "" => Category::NoCategory,
+                _ => Category::Unknown(s.to_string()),
+            }))
+    }
+}
+
+impl<'de> Deserialize<'de> for Category {
+    fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(des)?;
+        Ok(s.parse().unwrap_or_else(|x| match x {}))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Image {
+    #[serde(default, deserialize_with = "nullable")]
     pub id: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub thumbnail: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub url: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct License {
+    #[serde(default, deserialize_with = "nullable")]
     pub description: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub id: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub name: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub title: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub url: String,
 }
 
+// like in `ModFull`, old releases can miss any field, so a release that can't
+// be downloaded or ordered by version doesn't fail its whole mod
 #[derive(Debug, Clone, Deserialize)]
+#[serde(bound(deserialize = "INFO: Deserialize<'de> + Default"))]
 pub struct Release<INFO> {
+    #[serde(default, deserialize_with = "nullable")]
     pub download_url: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub file_name: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub info_json: INFO,
+    #[serde(default, deserialize_with = "nullable")]
     pub released_at: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub sha1: String,
+    #[serde(default, deserialize_with = "nullable")]
     pub version: String,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// Factorio assumes this version for mods whose `info.json` doesn't specify
+/// it.
+fn default_factorio_version() -> String {
+    "0.12".into()
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ShortInfoJson {
+    #[serde(default = "default_factorio_version")]
     pub factorio_version: String,
 }
 
+impl Default for ShortInfoJson {
+    fn default() -> Self {
+        Self {
+            factorio_version: default_factorio_version(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct FullInfoJson {
-    #[serde(deserialize_with = "dep_or_vec_dep")]
+    #[serde(default, deserialize_with = "dep_or_vec_dep")]
     pub dependencies: Vec<Dep>, // vec of strings, or single string
+    #[serde(default = "default_factorio_version")]
     pub factorio_version: String,
 }
 
+impl Default for FullInfoJson {
+    fn default() -> Self {
+        Self {
+            dependencies: vec![],
+            factorio_version: default_factorio_version(),
+        }
+    }
+}
+
+/// Treats `null` the same as missing field.
+fn nullable<'de, D, T>(des: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(des)?.unwrap_or_default())
+}
+
 fn dep_or_vec_dep<'de, D: Deserializer<'de>>(
     des: D,
 ) -> Result<Vec<Dep>, D::Error> {
@@ -90,8 +251,14 @@ fn dep_or_vec_dep<'de, D: Deserializer<'de>>(
         fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
             write!(f, "string or list of strings")
         }
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(vec![])
+        }
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(vec![])
+        }
         fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
-            Ok(vec![v.parse().unwrap()])
+            Ok(vec![Dep::parse_lenient(v)])
         }
         fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
         where
@@ -100,8 +267,8 @@ fn dep_or_vec_dep<'de, D: Deserializer<'de>>(
             Ok(<Vec<String>>::deserialize(
                 de::value::SeqAccessDeserializer::new(seq),
             )?
-            .into_iter()
-            .map(|x| x.parse().unwrap())
+            .iter()
+            .map(|x| Dep::parse_lenient(x))
             .collect())
         }
     }
@@ -113,7 +280,7 @@ pub struct Dep {
     pub original: String,
     pub prefix: DepPrefix,
     pub name: String,
-    pub version: String,
+    pub version: Option<VersionReq>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -125,9 +292,145 @@ pub enum DepPrefix {
     Required,
 }
 
-impl FromStr for Dep {
-    type Err = ();
-    fn from_str(original: &str) -> Result<Self, Self::Err> {
+impl DepPrefix {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            DepPrefix::Incompatible => "!",
+            DepPrefix::Optional => "?",
+            DepPrefix::HiddenOptional => "(?)",
+            DepPrefix::LoadOrderIndependent => "~",
+            DepPrefix::Required => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseDepErrorKind {
+    Empty,
+    MissingName,
+    /// Mod names can only have ascii letters, digits, `-`, `_` and spaces.
+    InvalidNameChar(char),
+    MissingVersion,
+    InvalidVersion,
+}
+
+/// Dependency string that doesn't follow factorio's dependency format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDepError {
+    pub input: String,
+    /// byte offset of the error in `input`
+    pub position: usize,
+    pub kind: ParseDepErrorKind,
+}
+
+impl fmt::Display for ParseDepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid dependency {:?} at {}: ",
+            self.input, self.position
+        )?;
+        match self.kind {
+            ParseDepErrorKind::Empty => write!(f, "empty dependency"),
+            ParseDepErrorKind::MissingName => write!(f, "missing mod name"),
+            ParseDepErrorKind::InvalidNameChar(c) => {
+                write!(f, "invalid character {c:?} in mod name")
+            }
+            ParseDepErrorKind::MissingVersion => {
+                write!(f, "missing version after operator")
+            }
+            ParseDepErrorKind::InvalidVersion => write!(f, "invalid version"),
+        }
+    }
+}
+
+impl core::error::Error for ParseDepError {}
+
+impl Dep {
+    /// Parses dependency with factorio's rules:
+    /// `[prefix] name [operator version]`, where prefix is one of `!`, `?`,
+    /// `(?)` or `~`, operator is one of `<`, `<=`, `=`, `>=` or `>`, and
+    /// version has two or three numbers. Spaces are allowed between all parts,
+    /// and inside of mod names (which some old mods have).
+    pub fn parse(original: &str) -> Result<Self, ParseDepError> {
+        let err = |rest: &str, kind| ParseDepError {
+            input: original.to_string(),
+            position: original.len() - rest.len(),
+            kind,
+        };
+        let mut s = original.trim_start();
+        if s.trim_end().is_empty() {
+            return Err(err(s, ParseDepErrorKind::Empty));
+        }
+
+        let prefix = [
+            DepPrefix::HiddenOptional,
+            DepPrefix::Incompatible,
+            DepPrefix::Optional,
+            DepPrefix::LoadOrderIndependent,
+        ]
+        .into_iter()
+        .find_map(|prefix| {
+            s.strip_prefix(prefix.as_str()).map(|rest| {
+                s = rest.trim_start();
+                prefix
+            })
+        })
+        .unwrap_or(DepPrefix::Required);
+
+        let name_end = s.find(['<', '=', '>']).unwrap_or(s.len());
+        let name = s[..name_end].trim_end();
+        if name.is_empty() {
+            return Err(err(s, ParseDepErrorKind::MissingName));
+        }
+        if let Some((i, c)) = name.char_indices().find(|&(_, c)| {
+            !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ' '))
+        }) {
+            return Err(err(&s[i..], ParseDepErrorKind::InvalidNameChar(c)));
+        }
+        s = &s[name_end..];
+
+        let version = if s.is_empty() {
+            None
+        } else {
+            let op = [
+                VersionOp::LessEq,
+                VersionOp::GreaterEq,
+                VersionOp::Less,
+                VersionOp::Eq,
+                VersionOp::Greater,
+            ]
+            .into_iter()
+            .find(|op| s.starts_with(op.as_str()))
+            .expect("name ends at operator");
+            s = s[op.as_str().len()..].trim_start();
+            let version_str = s.trim_end();
+            if version_str.is_empty() {
+                return Err(err(s, ParseDepErrorKind::MissingVersion));
+            }
+            if !version_str.chars().all(|c| c.is_ascii_digit() || c == '.') {
+                return Err(err(s, ParseDepErrorKind::InvalidVersion));
+            }
+            let version = version_str
+                .parse::<Version>()
+                .map_err(|_| err(s, ParseDepErrorKind::InvalidVersion))?;
+            Some(VersionReq { op, version })
+        };
+
+        Ok(Self {
+            original: original.to_string(),
+            prefix,
+            name: name.to_string(),
+            version,
+        })
+    }
+
+    /// Parses dependency the way this crate used to, by splitting it at first
+    /// `<`, `=` or `>`, and accepting anything around it.
+    ///
+    /// Returns name and version part as strings, with prefix removed. Kept to
+    /// compare with [`Dep::parse`].
+    pub fn parse_legacy(original: &str) -> (DepPrefix, String, String) {
         let mut s = original;
         s = s.trim();
         let prefix = if let Some(rest) = s.strip_prefix("(?)") {
@@ -147,11 +450,134 @@ impl FromStr for Dep {
         };
         let idx = s.find(['<', '=', '>']).unwrap_or(s.len());
         let (name, version) = s.split_at(idx);
-        Ok(Self {
-            original: original.to_string(),
-            prefix,
-            name: name.trim().to_string(),
-            version: version.trim().to_string(),
+        (prefix, name.trim().to_string(), version.trim().to_string())
+    }
+
+    /// Parses dependency with [`Dep::parse`], falling back to
+    /// [`Dep::parse_legacy`] for invalid ones, so metadata of mods with them
+    /// can still be loaded.
+    pub fn parse_lenient(original: &str) -> Self {
+        Self::parse(original).unwrap_or_else(|_| {
+            let (prefix, name, version) = Self::parse_legacy(original);
+            Self {
+                original: original.to_string(),
+                prefix,
+                name,
+                version: version.parse().ok(),
+            }
         })
     }
 }
+
+impl FromStr for Dep {
+    type Err = ParseDepError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Dep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.prefix != DepPrefix::Required {
+            write!(f, "{} ", self.prefix.as_str())?;
+        }
+        write!(f, "{}", self.name)?;
+        if let Some(version) = &self.version {
+            write!(f, " {version}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req(op: VersionOp, major: u16, minor: u16, sub: u16) -> VersionReq {
+        VersionReq {
+            op,
+            version: Version::new(major, minor, sub),
+        }
+    }
+
+    fn error_kind(s: &str) -> ParseDepErrorKind {
+        Dep::parse(s).unwrap_err().kind
+    }
+
+    #[test]
+    fn version_without_spaces() {
+        let dep = Dep::parse("name>=1.0").unwrap();
+        assert_eq!(dep.prefix, DepPrefix::Required);
+        assert_eq!(dep.name, "name");
+        assert_eq!(dep.version, Some(req(VersionOp::GreaterEq, 1, 0, 0)));
+        assert_eq!(dep.original, "name>=1.0");
+
+        let dep = Dep::parse("  name  <  0.18.3 ").unwrap();
+        assert_eq!(dep.name, "name");
+        assert_eq!(dep.version, Some(req(VersionOp::Less, 0, 18, 3)));
+    }
+
+    #[test]
+    fn prefixes() {
+        for (s, prefix) in [
+            ("? name", DepPrefix::Optional),
+            ("(?) name", DepPrefix::HiddenOptional),
+            ("! name", DepPrefix::Incompatible),
+            ("~ name", DepPrefix::LoadOrderIndependent),
+            ("?name", DepPrefix::Optional),
+            ("(?)name > 2.0", DepPrefix::HiddenOptional),
+        ] {
+            let dep = Dep::parse(s).unwrap();
+            assert_eq!(dep.prefix, prefix, "{s}");
+            assert_eq!(dep.name, "name", "{s}");
+        }
+    }
+
+    #[test]
+    fn names_with_spaces() {
+        let dep = Dep::parse("? Some Old Mod = 0.1.2").unwrap();
+        assert_eq!(dep.prefix, DepPrefix::Optional);
+        assert_eq!(dep.name, "Some Old Mod");
+        assert_eq!(dep.version, Some(req(VersionOp::Eq, 0, 1, 2)));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(error_kind(""), ParseDepErrorKind::Empty);
+        assert_eq!(error_kind("   "), ParseDepErrorKind::Empty);
+        assert_eq!(error_kind("? >= 1.0"), ParseDepErrorKind::MissingName);
+        assert_eq!(
+            error_kind("weird@name"),
+            ParseDepErrorKind::InvalidNameChar('@')
+        );
+        assert_eq!(error_kind("a >= "), ParseDepErrorKind::MissingVersion);
+        assert_eq!(error_kind("a >= 1.x"), ParseDepErrorKind::InvalidVersion);
+        assert_eq!(error_kind("a = = 1.0"), ParseDepErrorKind::InvalidVersion);
+        assert_eq!(
+            error_kind("a >= 1.2.3.4"),
+            ParseDepErrorKind::InvalidVersion
+        );
+        let e = Dep::parse("a >= 1.x").unwrap_err();
+        assert_eq!(e.position, 5);
+    }
+
+    #[test]
+    fn lenient_falls_back_to_legacy() {
+        let dep = Dep::parse_lenient("a >= 1.x");
+        assert_eq!(dep.name, "a");
+        assert_eq!(dep.version, None);
+        assert_eq!(dep.original, "a >= 1.x");
+
+        let dep = Dep::parse_lenient("! weird@name");
+        assert_eq!(dep.prefix, DepPrefix::Incompatible);
+        assert_eq!(dep.name, "weird@name");
+
+        let dep = Dep::parse_lenient("");
+        assert_eq!(dep.prefix, DepPrefix::Required);
+        assert_eq!(dep.name, "");
+
+        // valid ones are parsed the same as by `parse`
+        let s = "~ name >= 1.1";
+        assert_eq!(Dep::parse_lenient(s), Dep::parse(s).unwrap());
+    }
+}
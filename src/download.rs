@@ -1,12 +1,11 @@
-use core::{
-    sync::atomic::{AtomicUsize, Ordering},
-    time::Duration,
-};
-use std::{collections::BTreeMap, fs::File, path::Path, thread};
+//! Client for factorio mod portal.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::{collections::BTreeMap, fs::File, path::Path, sync::Mutex};
 
 use futures::{StreamExt, stream};
 use reqwest::{
-    Client, blocking as req_blocking,
+    Client, Response, blocking as req_blocking,
     header::{self, HeaderMap, HeaderValue},
 };
 use tokio::{
@@ -14,7 +13,9 @@ use tokio::{
     runtime::Runtime,
 };
 
-use crate::{APP_ID, Error, USER_AGENT, deserialization::LatestRelease};
+use crate::{
+    Error, USER_AGENT, cache::images::ImageCache, deserialization::Release,
+};
 
 #[derive(serde::Deserialize)]
 pub struct PlayerCreds {
@@ -24,12 +25,41 @@ pub struct PlayerCreds {
     token: String,
 }
 
+impl PlayerCreds {
+    /// Reads credentials of player logged in given factorio instance.
+    pub fn load(factorio_instance: &Path) -> Result<Self, Error> {
+        let path = factorio_instance.join("player-data.json");
+        let file = File::open(&path).map_err(|e| Error::Instance {
+            path: path.clone(),
+            message: e.to_string(),
+        })?;
+        simd_json::from_reader(file).map_err(|_| Error::Instance {
+            path,
+            message: "player isn't logged in".into(),
+        })
+    }
+}
+
+/// Turns error status of a response into [`Error::Status`].
+fn check_status(resp: Response) -> Result<Response, Error> {
+    let status = resp.status();
+    if status.is_client_error() || status.is_server_error() {
+        let mut url = resp.url().clone();
+        url.set_query(None);
+        return Err(Error::Status {
+            url: url.into(),
+            status,
+        });
+    }
+    Ok(resp)
+}
+
 pub async fn toggle_bookmark(
     req: &Client,
     creds: &PlayerCreds,
     mod_name: &str,
     on: bool,
-) -> reqwest::Result<()> {
+) -> Result<(), Error> {
     let state = match on {
         true => "on",
         false => "off",
@@ -39,80 +69,143 @@ pub async fn toggle_bookmark(
         /toggle?username={}&token={}&mod={}&state={}",
         creds.username, creds.token, mod_name, state
     );
-    req.post(url).send().await?.error_for_status()?;
+    check_status(req.post(url).send().await?)?;
     Ok(())
 }
 
 pub async fn download_bookmark_list(
     req: &Client,
     creds: &PlayerCreds,
-) -> reqwest::Result<String> {
+) -> Result<String, Error> {
     let url = format!(
         "https://mods.factorio.com/api/bookmarks?username={}&token={}",
         creds.username, creds.token
     );
-    req.get(url).send().await?.error_for_status()?.text().await
+    Ok(check_status(req.get(url).send().await?)?.text().await?)
 }
 
-pub fn download_mod_list() -> reqwest::Result<String> {
+pub fn download_mod_list() -> Result<String, Error> {
     let url = "https://mods.factorio.com/api/mods?page_size=max";
-    req_blocking::get(url)?.text()
+    let resp = req_blocking::get(url)?;
+    let status = resp.status();
+    if status.is_client_error() || status.is_server_error() {
+        return Err(Error::Status {
+            url: url.into(),
+            status,
+        });
+    }
+    Ok(resp.text()?)
 }
 
-async fn download_mod_meta_full(req: &Client, name: &str) -> Result<(), Error> {
+async fn download_mod_meta_full(
+    req: &Client,
+    mods_dir: &Path,
+    name: &str,
+) -> Result<(), Error> {
     let url = format!("https://mods.factorio.com/api/mods/{name}/full");
-    let resp = req
-        .execute(req.get(url).build()?)
-        .await?
-        .error_for_status()?
+    let resp = check_status(req.execute(req.get(url).build()?).await?)?
         .text()
         .await?;
-    tokio_fs::write(
-        eframe::storage_dir(APP_ID)
-            .unwrap()
-            .join("mods")
-            .join(format!("{name}.json")),
-        resp,
-    )
-    .await?;
+    tokio_fs::write(mods_dir.join(format!("{name}.json")), resp).await?;
     Ok(())
 }
 
-pub fn download_mods_meta_full<'a>(
-    mod_list: impl Iterator<Item = &'a str> + Clone,
-) {
-    static COUNTER: AtomicUsize = AtomicUsize::new(0);
-
-    let mod_count = mod_list.clone().count();
-    let rt = Runtime::new().unwrap();
-
+fn client() -> Result<Client, Error> {
     let mut headers = HeaderMap::new();
     headers.insert(header::USER_AGENT, HeaderValue::from_static(USER_AGENT));
-    let req = Client::builder().default_headers(headers).build().unwrap();
-
-    // todo: zip
+    Ok(Client::builder().default_headers(headers).build()?)
+}
 
-    let mut futures = vec![];
-    for name in mod_list {
-        let req = &req;
-        futures.push(async move {
-            download_mod_meta_full(req, name).await.unwrap();
-            COUNTER.fetch_add(1, Ordering::Relaxed);
+/// Downloads a single image, blocking the thread.
+pub fn download_image(url: &str) -> Result<Vec<u8>, Error> {
+    let req = req_blocking::Client::builder()
+        .user_agent(USER_AGENT)
+        .build()?;
+    let resp = req.get(url).send()?;
+    let status = resp.status();
+    if status.is_client_error() || status.is_server_error() {
+        return Err(Error::Status {
+            url: url.into(),
+            status,
         });
     }
+    Ok(resp.bytes()?.to_vec())
+}
 
-    let _ui = thread::spawn(move || {
-        loop {
-            let v = COUNTER.load(Ordering::Relaxed);
-            eprint!("{:>12} {v}/{mod_count} mods metadata\r", "Downloading");
-            if v >= mod_count {
-                eprintln!();
-                break;
+/// Progress of downloading many mods, shared with whoever shows it.
+#[derive(Debug, Default)]
+pub struct Progress {
+    pub done: AtomicUsize,
+    /// 0 until number of mods to download is known
+    pub total: AtomicUsize,
+}
+
+/// Runs `f` for every mod concurrently, while updating `progress`.
+///
+/// Mods that failed don't stop the rest, and their errors are returned.
+fn run_with_progress<'a, F>(
+    mod_list: impl Iterator<Item = &'a str> + Clone,
+    progress: &Progress,
+    f: impl Fn(&'a str) -> F,
+) -> Result<Vec<Error>, Error>
+where
+    F: Future<Output = Result<(), Error>>,
+{
+    let counter = &progress.done;
+    let errors = Mutex::new(vec![]);
+    counter.store(0, Ordering::Relaxed);
+    progress
+        .total
+        .store(mod_list.clone().count(), Ordering::Relaxed);
+    let rt = Runtime::new()?;
+
+    rt.block_on(stream::iter(mod_list).for_each_concurrent(64, |name| {
+        let fut = f(name);
+        let errors = &errors;
+        async move {
+            if let Err(e) = fut.await {
+                errors.lock().unwrap().push(Error::Mod {
+                    name: name.into(),
+                    source: Box::new(e),
+                });
             }
-            thread::sleep(Duration::from_secs(1));
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }));
+    Ok(errors.into_inner().unwrap())
+}
+
+/// Downloads full metadata of given mods into `mods_dir`.
+///
+/// Returns errors of mods that couldn't be downloaded.
+pub fn download_mods_meta_full<'a>(
+    mods_dir: &Path,
+    mod_list: impl Iterator<Item = &'a str> + Clone,
+    progress: &Progress,
+) -> Result<Vec<Error>, Error> {
+    let req = client()?;
+    // todo: zip
+    run_with_progress(mod_list, progress, |name| {
+        download_mod_meta_full(&req, mods_dir, name)
+    })
+}
+
+/// Downloads images at given urls into `cache`.
+///
+/// Returns errors of images that couldn't be downloaded.
+pub fn download_images<'a>(
+    cache: &ImageCache,
+    urls: impl Iterator<Item = &'a str> + Clone,
+    progress: &Progress,
+) -> Result<Vec<Error>, Error> {
+    let req = client()?;
+    run_with_progress(urls, progress, |url| {
+        let req = &req;
+        async move {
+            let resp = check_status(req.get(url).send().await?)?;
+            cache.insert(url, &resp.bytes().await?)
         }
-    });
-    rt.block_on(stream::iter(futures).for_each_concurrent(64, |x| x));
+    })
 }
 
 async fn download_mod(
@@ -126,64 +219,71 @@ async fn download_mod(
         "https://mods.factorio.com/{}?username={}&token={}",
         download_url, creds.username, creds.token
     );
-    let resp = req
-        .execute(req.get(url).build()?)
-        .await?
-        .error_for_status()?
+    let resp = check_status(req.execute(req.get(url).build()?).await?)?
         .bytes()
         .await?;
     tokio_fs::write(mods_folder.join(file_name), resp).await?;
     Ok(())
 }
 
-pub fn download_mods<'a>(
+/// Downloads given releases of mods (usually the latest ones) into `mods`
+/// folder of factorio instance, using credentials of player logged in that
+/// instance, while updating `progress`.
+///
+/// Returns errors of mods that couldn't be downloaded.
+pub fn download_mods<'a, INFO: Sync>(
     factorio_instance: &Path,
     mod_list: impl Iterator<Item = &'a str> + Clone,
-    mod_version_list: &BTreeMap<&'a str, Option<&'a LatestRelease>>,
-) -> Result<(), Error> {
-    static COUNTER: AtomicUsize = AtomicUsize::new(0);
-
-    let player_creds: PlayerCreds = simd_json::from_reader(File::open(
-        factorio_instance.join("player-data.json"),
-    )?)?;
-
-    let mod_count = mod_list.clone().count();
-
-    let mut headers = HeaderMap::new();
-    headers.insert(header::USER_AGENT, HeaderValue::from_static(USER_AGENT));
-    let req = Client::builder().default_headers(headers).build()?;
-    let rt = Runtime::new()?;
+    mod_version_list: &BTreeMap<&'a str, Option<&'a Release<INFO>>>,
+    progress: &Progress,
+) -> Result<Vec<Error>, Error> {
+    let player_creds = PlayerCreds::load(factorio_instance)?;
+    let mods_folder = factorio_instance.join("mods");
+    if !mods_folder.is_dir() {
+        return Err(Error::Instance {
+            path: factorio_instance.into(),
+            message: "missing mods folder".into(),
+        });
+    }
+    download_releases(
+        &player_creds,
+        &mods_folder,
+        mod_list,
+        mod_version_list,
+        progress,
+    )
+}
 
-    let mut futures = vec![];
-    for name in mod_list {
-        let player_creds = &player_creds;
+/// Downloads given releases of mods into `mods_folder`, while updating
+/// `progress`.
+///
+/// Returns errors of mods that couldn't be downloaded.
+pub fn download_releases<'a, INFO: Sync>(
+    player_creds: &PlayerCreds,
+    mods_folder: &Path,
+    mod_list: impl Iterator<Item = &'a str> + Clone,
+    mod_version_list: &BTreeMap<&'a str, Option<&'a Release<INFO>>>,
+    progress: &Progress,
+) -> Result<Vec<Error>, Error> {
+    let req = client()?;
+    run_with_progress(mod_list, progress, |name| {
+        let release = mod_version_list.get(name).copied().flatten();
         let req = &req;
-        futures.push(async move {
-            let release = mod_version_list[name].unwrap();
+        async move {
+            // old releases can miss where to download them from
+            let release = release
+                .filter(|x| {
+                    !x.file_name.is_empty() && !x.download_url.is_empty()
+                })
+                .ok_or_else(|| Error::NoRelease(name.into()))?;
             download_mod(
                 req,
                 &release.file_name,
                 &release.download_url,
-                &factorio_instance.join("mods"),
+                mods_folder,
                 player_creds,
             )
             .await
-            .unwrap();
-            COUNTER.fetch_add(1, Ordering::Relaxed);
-        });
-    }
-
-    let _ui = thread::spawn(move || {
-        loop {
-            let v = COUNTER.load(Ordering::Relaxed);
-            eprint!("{:>12} {v}/{mod_count} mods\r", "Downloading");
-            if v >= mod_count {
-                eprintln!();
-                break;
-            }
-            thread::sleep(Duration::from_secs(1));
         }
-    });
-    rt.block_on(stream::iter(futures).for_each_concurrent(64, |x| x));
-    Ok(())
+    })
 }
@@ -0,0 +1,103 @@
+use core::fmt;
+use std::{io, path::PathBuf};
+
+use reqwest::StatusCode;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Request to the mod portal couldn't be sent, or its response couldn't be
+    /// received.
+    Network(reqwest::Error),
+    /// Mod portal responded with error status.
+    ///
+    /// Query is stripped from `url`, as it can contain player's token.
+    Status {
+        url: String,
+        status: StatusCode,
+    },
+    Io(io::Error),
+    NoStorageDir,
+    /// Cached file isn't valid json, or doesn't match expected format.
+    CacheCorrupt {
+        path: PathBuf,
+        message: String,
+    },
+    /// Full metadata of a mod isn't in the cache.
+    NotCached(String),
+    /// Field of mod's metadata couldn't be parsed.
+    Parse {
+        mod_name: String,
+        field: String,
+        message: String,
+    },
+    /// Directory doesn't look like a factorio instance, or player isn't logged
+    /// in there.
+    Instance {
+        path: PathBuf,
+        message: String,
+    },
+    NoRelease(String),
+    /// Error with processing a single mod.
+    Mod {
+        name: String,
+        source: Box<Error>,
+    },
+    Lua(String),
+    Gui(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Network(e) => write!(f, "network error: {e}"),
+            Error::Status { url, status } => {
+                write!(f, "mod portal responded with {status} for {url}")
+            }
+            Error::Io(e) => write!(f, "io error: {e}"),
+            Error::NoStorageDir => write!(f, "couldn't find storage directory"),
+            Error::CacheCorrupt { path, message } => {
+                write!(f, "corrupted cache file {}: {message}", path.display())
+            }
+            Error::NotCached(name) => write!(f, "{name} isn't cached"),
+            Error::Parse {
+                mod_name,
+                field,
+                message,
+            } => write!(f, "couldn't parse `{field}` of {mod_name}: {message}"),
+            Error::Instance { path, message } => {
+                write!(
+                    f,
+                    "invalid factorio instance {}: {message}",
+                    path.display()
+                )
+            }
+            Error::NoRelease(name) => write!(f, "{name} has no releases"),
+            Error::Mod { name, source } => write!(f, "{name}: {source}"),
+            Error::Lua(e) => write!(f, "lua error: {e}"),
+            Error::Gui(e) => write!(f, "gui error: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::Network(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Mod { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Network(e.without_url())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
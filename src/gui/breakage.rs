@@ -0,0 +1,212 @@
+//! Central panel listing mods that `find_broken_mods` reports, and why.
+
+use std::path::PathBuf;
+
+use eframe::egui::{self, Color32};
+use factorio_crater::{
+    analysis::{Analysis, ModStatus},
+    version::Version,
+};
+
+use crate::gui::{Gui, View, analysis, combo_box, tree::status_color};
+
+/// Kind of breakage listed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Kind {
+    #[default]
+    Broken,
+    Deprecated,
+    MissingDependency,
+    Cyclic,
+    Incompatible,
+}
+
+impl Kind {
+    const ALL: &[Kind] = &[
+        Kind::Broken,
+        Kind::Deprecated,
+        Kind::MissingDependency,
+        Kind::Cyclic,
+        Kind::Incompatible,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Broken => "broken",
+            Kind::Deprecated => "deprecated",
+            Kind::MissingDependency => "missing dependency",
+            Kind::Cyclic => "cyclic dependencies",
+            Kind::Incompatible => "incompatible dependencies",
+        }
+    }
+
+    fn status(self) -> ModStatus {
+        match self {
+            Kind::Broken | Kind::MissingDependency => ModStatus::Broken,
+            Kind::Deprecated => ModStatus::Deprecated,
+            Kind::Cyclic => ModStatus::Unresolved,
+            Kind::Incompatible => ModStatus::Incompatible,
+        }
+    }
+
+    /// Mods of this kind, the same as in reports written by the CLI.
+    fn mods(self, analysis: &Analysis) -> Vec<&str> {
+        let report = &analysis.report;
+        match self {
+            Kind::Broken => report.reported_broken().map(|(x, _)| x).collect(),
+            Kind::Deprecated => {
+                report.deprecated.iter().map(|x| x.as_str()).collect()
+            }
+            Kind::MissingDependency => {
+                report.typod.keys().map(|x| x.as_str()).collect()
+            }
+            Kind::Cyclic => {
+                report.unresolved.iter().map(|x| x.as_str()).collect()
+            }
+            Kind::Incompatible => {
+                report.uninstallable.keys().map(|x| x.as_str()).collect()
+            }
+        }
+    }
+}
+
+pub struct Breakage {
+    kind: Kind,
+    factorio_version: Option<String>,
+    export_dir: String,
+    /// directory of last export, or why it failed
+    exported: Option<Result<PathBuf, String>>,
+}
+
+impl Default for Breakage {
+    fn default() -> Self {
+        Self {
+            kind: Kind::default(),
+            factorio_version: None,
+            export_dir: ".".into(),
+            exported: None,
+        }
+    }
+}
+
+impl Gui {
+    pub(super) fn draw_breakage(&mut self, ui: &mut egui::Ui) {
+        let state = &mut self.breakage;
+        ui.horizontal(|ui| {
+            ui.label("factorio version");
+            combo_box(
+                ui,
+                "breakage_version",
+                &mut state.factorio_version,
+                &self.factorio_versions,
+            );
+            ui.checkbox(&mut self.space_age, "space age").on_hover_text(
+                "whether expansion's built-in mods are available",
+            );
+        });
+        let analysis = analysis(&mut self.analysis, &self.mods, self.space_age);
+        let report = &analysis.report;
+        // compared as versions, as reports do
+        let version_of = |name: &str| {
+            report
+                .mods
+                .get(name)?
+                .factorio_version
+                .parse::<Version>()
+                .ok()
+        };
+        let version = state.factorio_version.as_ref().map(|x| x.parse().ok());
+        let matches =
+            |name: &&str| version.is_none_or(|x| version_of(name) == x);
+
+        ui.horizontal_wrapped(|ui| {
+            for &kind in Kind::ALL {
+                let count =
+                    kind.mods(analysis).into_iter().filter(matches).count();
+                ui.selectable_value(
+                    &mut state.kind,
+                    kind,
+                    format!("{} ({count})", kind.as_str()),
+                );
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut state.export_dir)
+                    .hint_text("directory for reports"),
+            );
+            if ui.button("export reports").clicked() {
+                let dir = PathBuf::from(state.export_dir.trim());
+                state.exported = Some(
+                    report
+                        .write_reports(&dir)
+                        .map(|()| dir)
+                        .map_err(|e| e.to_string()),
+                );
+            }
+            match &state.exported {
+                Some(Ok(dir)) => {
+                    ui.label(format!("written into {}", dir.display()));
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(Color32::LIGHT_RED, e);
+                }
+                None => (),
+            }
+        });
+        if state.kind == Kind::Broken {
+            ui.weak(
+                "as in reports, mods for 2.0 are only listed if they need \
+                unavailable built-in mods",
+            );
+        }
+        ui.separator();
+
+        let mods = state
+            .kind
+            .mods(analysis)
+            .into_iter()
+            .filter(matches)
+            .collect::<Vec<_>>();
+        let color = status_color(state.kind.status());
+        let mut clicked = None;
+        let row_height = ui.text_style_height(&egui::TextStyle::Body)
+            + ui.spacing().item_spacing.y;
+        egui::ScrollArea::vertical().auto_shrink(false).show_rows(
+            ui,
+            row_height,
+            mods.len(),
+            |ui, range| {
+                for &name in &mods[range] {
+                    ui.horizontal(|ui| {
+                        let reasons = report.reasons(name);
+                        let link =
+                            ui.link(egui::RichText::new(name).color(color));
+                        let link = if reasons.is_empty() {
+                            link
+                        } else {
+                            link.on_hover_text(reasons.join("\n"))
+                        };
+                        if link.clicked() {
+                            clicked = Some(name.to_string());
+                        }
+                        if let Some(version) = version_of(name) {
+                            ui.weak(format!("for {version}"));
+                        }
+                        if let Some(reason) = reasons.first() {
+                            ui.label(reason);
+                        }
+                        if reasons.len() > 1 {
+                            ui.weak(format!("and {} more", reasons.len() - 1));
+                        }
+                    });
+                }
+            },
+        );
+        if let Some(name) = clicked {
+            self.view = View::Details;
+            self.jump_to(name);
+            ui.ctx().request_repaint();
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Central panel with details of selected mod.
+
+use std::collections::BTreeMap;
+
+use eframe::egui::{self, Color32, RichText, Sense, Vec2};
+use factorio_crater::{
+    INTERNAL_MODS,
+    cache::images::asset_url,
+    changelog::{Changelog, ChangelogError},
+    deserialization::{DepPrefix, ModFull},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::gui::{
+    Gui, SIZE, analysis, markdown::Markdown, star_button, tree::draw_trees,
+};
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum DetailTab {
+    #[default]
+    Description,
+    Releases,
+    Dependencies,
+    DependencyTree,
+    Changelog,
+}
+
+impl DetailTab {
+    const ALL: &[DetailTab] = &[
+        DetailTab::Description,
+        DetailTab::Releases,
+        DetailTab::Dependencies,
+        DetailTab::DependencyTree,
+        DetailTab::Changelog,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            DetailTab::Description => "description",
+            DetailTab::Releases => "releases",
+            DetailTab::Dependencies => "dependencies",
+            DetailTab::DependencyTree => "dependency tree",
+            DetailTab::Changelog => "changelog",
+        }
+    }
+}
+
+fn prefix_color(prefix: DepPrefix) -> Option<Color32> {
+    match prefix {
+        DepPrefix::Required => None,
+        DepPrefix::Optional | DepPrefix::HiddenOptional => Some(Color32::GRAY),
+        DepPrefix::Incompatible => Some(Color32::LIGHT_RED),
+        DepPrefix::LoadOrderIndependent => Some(Color32::LIGHT_BLUE),
+    }
+}
+
+pub(super) fn prefix_description(prefix: DepPrefix) -> &'static str {
+    match prefix {
+        DepPrefix::Required => "required",
+        DepPrefix::Optional => "optional",
+        DepPrefix::HiddenOptional => "hidden optional",
+        DepPrefix::Incompatible => "incompatible",
+        DepPrefix::LoadOrderIndependent => "doesn't affect load order",
+    }
+}
+
+/// Description and changelog of selected mod, parsed once when it's selected.
+pub struct Parsed {
+    name: String,
+    description: Markdown,
+    changelog: Option<Result<Changelog, ChangelogError>>,
+}
+
+/// Returns parsed texts of `m`, parsing them if `parsed` is for another mod.
+fn parsed<'a>(parsed: &'a mut Option<Parsed>, m: &ModFull) -> &'a Parsed {
+    if parsed.as_ref().is_none_or(|x| x.name != m.name) {
+        *parsed = Some(Parsed {
+            name: m.name.clone(),
+            description: Markdown::parse(
+                m.description.as_deref().unwrap_or_default(),
+            ),
+            changelog: m.changelog.as_deref().map(Changelog::parse),
+        });
+    }
+    parsed.as_ref().unwrap()
+}
+
+impl Gui {
+    pub(super) fn draw_details(&mut self, ui: &mut egui::Ui) {
+        let selected = self.selected_mod.as_ref();
+        let Some(m) = selected.and_then(|x| self.mods.get(x)) else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            star_button(ui, &mut self.starred, &m.name);
+            ui.heading(&m.title);
+        });
+        ui.label(format!("by {}", &m.owner));
+        draw_links(ui, m);
+        ui.horizontal(|ui| {
+            for &tab in DetailTab::ALL {
+                ui.selectable_value(&mut self.detail_tab, tab, tab.as_str());
+            }
+        });
+        ui.separator();
+
+        // mod to select after drawing, as `m` borrows `self`
+        let mut jump_to = None;
+        egui::ScrollArea::vertical().show(ui, |ui| match self.detail_tab {
+            DetailTab::Description => {
+                if !m.images.is_empty() {
+                    draw_images(ui, m, &mut self.selected_image);
+                }
+                parsed(&mut self.parsed, m).description.show(ui);
+            }
+            DetailTab::Releases => draw_releases(ui, m),
+            DetailTab::Dependencies => {
+                jump_to = draw_dependencies(
+                    ui,
+                    &self.mods,
+                    m,
+                    &mut self.selected_release,
+                );
+            }
+            DetailTab::DependencyTree => {
+                let analysis =
+                    analysis(&mut self.analysis, &self.mods, self.space_age);
+                jump_to = draw_trees(ui, analysis, &m.name);
+            }
+            DetailTab::Changelog => {
+                draw_changelog(ui, m, &parsed(&mut self.parsed, m).changelog)
+            }
+        });
+        if let Some(name) = jump_to {
+            self.jump_to(name);
+            // list is drawn before this panel
+            ui.ctx().request_repaint();
+        }
+    }
+}
+
+/// Draws dependencies of chosen release, and returns dependency that was
+/// clicked.
+fn draw_dependencies(
+    ui: &mut egui::Ui,
+    mods: &BTreeMap<String, ModFull>,
+    m: &ModFull,
+    selected: &mut Option<String>,
+) -> Option<String> {
+    let mut clicked = None;
+    let release = selected
+        .as_ref()
+        .and_then(|x| m.releases.iter().find(|r| &r.version == x))
+        .or_else(|| m.latest_release());
+    let Some(release) = release else {
+        ui.label("no releases");
+        return None;
+    };
+    egui::ComboBox::from_label("release")
+        .selected_text(&release.version)
+        .show_ui(ui, |ui| {
+            for r in m.releases.iter().rev() {
+                let checked = r.version == release.version;
+                if ui.selectable_label(checked, &r.version).clicked() {
+                    *selected = Some(r.version.clone());
+                }
+            }
+        });
+    ui.label(format!(
+        "for factorio {}",
+        release.info_json.factorio_version
+    ));
+    egui::Grid::new("dependencies")
+        .striped(true)
+        .show(ui, |ui| {
+            for dep in &release.info_json.dependencies {
+                let mut kind = RichText::new(prefix_description(dep.prefix));
+                if let Some(color) = prefix_color(dep.prefix) {
+                    kind = kind.color(color);
+                }
+                ui.label(kind);
+                if mods.contains_key(&dep.name) {
+                    if ui.link(&dep.name).clicked() {
+                        clicked = Some(dep.name.clone());
+                    }
+                } else if INTERNAL_MODS.contains(&dep.name.as_str()) {
+                    ui.label(format!("{} (built-in)", dep.name));
+                } else {
+                    ui.label(
+                        RichText::new(format!("{} (missing)", dep.name))
+                            .color(Color32::LIGHT_RED),
+                    );
+                }
+                ui.label(
+                    dep.version
+                        .as_ref()
+                        .map(|x| x.to_string())
+                        .unwrap_or_default(),
+                );
+                ui.end_row();
+            }
+        });
+    clicked
+}
+
+fn draw_links(ui: &mut egui::Ui, m: &ModFull) {
+    ui.horizontal_wrapped(|ui| {
+        ui.hyperlink_to(
+            "mod portal",
+            format!("https://mods.factorio.com/mod/{}", m.name),
+        );
+        if let Some(license) = &m.license {
+            let title = format!("license: {}", license.title);
+            if license.url.is_empty() {
+                ui.label(title).on_hover_text(&license.description);
+            } else {
+                ui.hyperlink_to(title, &license.url)
+                    .on_hover_text(&license.description);
+            }
+        }
+        if let Some(source) = m.source_url.as_ref().filter(|x| !x.is_empty()) {
+            ui.hyperlink_to("source", source);
+        }
+        if !m.homepage.is_empty() {
+            ui.hyperlink_to("homepage", &m.homepage);
+        }
+    });
+}
+
+fn draw_images(
+    ui: &mut egui::Ui,
+    m: &ModFull,
+    selected_image: &mut Option<String>,
+) {
+    ui.group(|ui| {
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                for image in &m.images {
+                    let (id, rect) = ui.allocate_space(Vec2::splat(SIZE));
+                    ui.put(
+                        rect,
+                        egui::Image::new(asset_url(&image.thumbnail))
+                            .max_size(Vec2::splat(SIZE)),
+                    );
+                    if ui.interact(rect, id, Sense::click()).clicked() {
+                        *selected_image = Some(image.url.clone());
+                    }
+                }
+            });
+        });
+        if let Some(image) = selected_image {
+            ui.image(asset_url(image));
+        }
+    });
+}
+
+fn draw_changelog(
+    ui: &mut egui::Ui,
+    m: &ModFull,
+    changelog: &Option<Result<Changelog, ChangelogError>>,
+) {
+    match changelog {
+        None => {
+            ui.label("no changelog");
+        }
+        Some(Ok(changelog)) => {
+            for (i, version) in changelog.versions.iter().enumerate() {
+                let mut title = version.version.to_string();
+                if let Some(date) = &version.date {
+                    title = format!("{title} ({date})");
+                }
+                egui::CollapsingHeader::new(title)
+                    .id_salt((&m.name, i))
+                    .default_open(i == 0)
+                    .show(ui, |ui| {
+                        for category in &version.categories {
+                            ui.strong(&category.name);
+                            for entry in &category.entries {
+                                ui.horizontal_wrapped(|ui| {
+                                    ui.label("•");
+                                    ui.label(entry);
+                                });
+                            }
+                        }
+                    });
+            }
+        }
+        Some(Err(e)) => {
+            ui.colored_label(
+                Color32::LIGHT_RED,
+                format!("factorio would reject this changelog: {e}"),
+            );
+            let changelog = m.changelog.as_deref().unwrap_or_default();
+            ui.label(RichText::new(changelog).monospace());
+        }
+    }
+}
+
+fn draw_releases(ui: &mut egui::Ui, m: &ModFull) {
+    egui::Grid::new("releases").striped(true).show(ui, |ui| {
+        for header in ["version", "factorio", "released", "file", "sha1"] {
+            ui.strong(header);
+        }
+        ui.end_row();
+        for r in m.releases.iter().rev() {
+            ui.label(&r.version);
+            ui.label(&r.info_json.factorio_version);
+            ui.label(&r.released_at);
+            ui.label(&r.file_name);
+            ui.label(RichText::new(&r.sha1).monospace());
+            ui.end_row();
+        }
+    });
+}
@@ -0,0 +1,104 @@
+//! Loading mod portal images through the on-disk image cache, so they're
+//! downloaded only once, and work offline.
+
+use std::{collections::HashMap, sync::Arc, task::Poll, thread};
+
+use eframe::egui::{
+    self,
+    load::{Bytes, BytesLoadResult, BytesLoader, BytesPoll, LoadError},
+    mutex::Mutex,
+};
+use factorio_crater::{
+    cache::images::{ASSETS_HOST, ImageCache},
+    download::download_image,
+};
+
+type Entry = Poll<Result<Arc<[u8]>, String>>;
+
+pub struct CachedImageLoader {
+    cache: Arc<ImageCache>,
+    /// images loaded during this session
+    loaded: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl CachedImageLoader {
+    pub const ID: &'static str = egui::generate_loader_id!(CachedImageLoader);
+
+    pub fn new(cache: ImageCache) -> Self {
+        Self {
+            cache: Arc::new(cache),
+            loaded: Arc::default(),
+        }
+    }
+}
+
+impl BytesLoader for CachedImageLoader {
+    fn id(&self) -> &str {
+        Self::ID
+    }
+
+    fn load(&self, ctx: &egui::Context, uri: &str) -> BytesLoadResult {
+        // other images are left to egui_extras' http loader
+        if !uri.starts_with(&format!("{ASSETS_HOST}/")) {
+            return Err(LoadError::NotSupported);
+        }
+        let mut loaded = self.loaded.lock();
+        match loaded.get(uri) {
+            Some(Poll::Ready(Ok(bytes))) => {
+                return Ok(BytesPoll::Ready {
+                    size: None,
+                    bytes: Bytes::Shared(bytes.clone()),
+                    mime: None,
+                });
+            }
+            Some(Poll::Ready(Err(e))) => {
+                return Err(LoadError::Loading(e.clone()));
+            }
+            Some(Poll::Pending) => {
+                return Ok(BytesPoll::Pending { size: None });
+            }
+            None => (),
+        }
+        loaded.insert(uri.into(), Poll::Pending);
+        drop(loaded);
+
+        let (ctx, cache, loaded, uri) = (
+            ctx.clone(),
+            self.cache.clone(),
+            self.loaded.clone(),
+            uri.to_string(),
+        );
+        thread::spawn(move || {
+            let result = match cache.get(&uri) {
+                Some(bytes) => Ok(bytes),
+                None => download_image(&uri).inspect(|bytes| {
+                    // failing to cache it shouldn't stop showing it
+                    let _ = cache.insert(&uri, bytes);
+                }),
+            };
+            let result = result.map(Arc::from).map_err(|e| e.to_string());
+            loaded.lock().insert(uri, Poll::Ready(result));
+            ctx.request_repaint();
+        });
+        Ok(BytesPoll::Pending { size: None })
+    }
+
+    fn forget(&self, uri: &str) {
+        self.loaded.lock().remove(uri);
+    }
+
+    fn forget_all(&self) {
+        self.loaded.lock().clear();
+    }
+
+    fn byte_size(&self) -> usize {
+        self.loaded
+            .lock()
+            .values()
+            .map(|x| match x {
+                Poll::Ready(Ok(bytes)) => bytes.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+}
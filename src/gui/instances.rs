@@ -0,0 +1,361 @@
+//! Central panel managing mods installed in factorio instances.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use eframe::egui::{self, Color32};
+use factorio_crater::{
+    Error,
+    deserialization::{FullInfoJson, ModFull, Release},
+    instance::{InstalledMod, Instance, disabled_mods, missing_mods},
+    version::Version,
+};
+
+use crate::gui::Gui;
+
+type InstallResult = Result<Vec<Error>, Error>;
+
+#[derive(Default)]
+pub struct Instances {
+    pub paths: Vec<PathBuf>,
+    pub selected: usize,
+    /// path typed into "add instance" field
+    new_path: String,
+    /// selected instance, `None` if it needs reloading
+    loaded: Option<Result<Rc<Loaded>, Error>>,
+    /// label of download running in background, and its result
+    task: Option<(String, Receiver<InstallResult>)>,
+    errors: Vec<String>,
+    /// mod waiting for confirmation of its removal
+    confirm_remove: Option<String>,
+}
+
+/// Selected instance, loaded once instead of on every frame.
+struct Loaded {
+    instance: Instance,
+    installed: Vec<InstalledMod>,
+    /// latest releases newer than installed ones
+    updates: BTreeMap<String, Release<FullInfoJson>>,
+}
+
+impl Loaded {
+    fn load(
+        path: &Path,
+        mods: &BTreeMap<String, ModFull>,
+    ) -> Result<Self, Error> {
+        let instance = Instance::open(path)?;
+        let installed = instance.installed_mods()?;
+        let updates = installed
+            .iter()
+            .filter_map(|x| Some((x.name.clone(), update_of(mods, x)?.clone())))
+            .collect();
+        Ok(Self {
+            instance,
+            installed,
+            updates,
+        })
+    }
+}
+
+/// Latest release of a mod, if it's newer than installed one.
+fn update_of<'a>(
+    mods: &'a BTreeMap<String, ModFull>,
+    installed: &InstalledMod,
+) -> Option<&'a Release<FullInfoJson>> {
+    let latest = mods.get(&installed.name)?.latest_release()?;
+    let version = latest.version.parse::<Version>().ok()?;
+    (Some(version) > installed.version).then_some(latest)
+}
+
+impl Instances {
+    /// Loads selected instance again on next frame, e.g. after mods
+    /// metadata changed.
+    pub fn reload(&mut self) {
+        self.loaded = None;
+    }
+
+    /// Reports result of a finished action, and reloads installed mods.
+    fn finish(&mut self, result: Result<(), Error>) {
+        if let Err(e) = result {
+            self.errors.push(e.to_string());
+        }
+        self.reload();
+    }
+
+    /// Installs given releases into selected instance on a background thread,
+    /// and enables mods in `enable` afterwards.
+    fn spawn_install(
+        &mut self,
+        ctx: &egui::Context,
+        label: String,
+        releases: BTreeMap<String, Release<FullInfoJson>>,
+        enable: Vec<String>,
+    ) {
+        let Some(path) = self.paths.get(self.selected).cloned() else {
+            return;
+        };
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let releases = releases
+                .iter()
+                .map(|(name, release)| (name.as_str(), Some(release)))
+                .collect();
+            let result = Instance::open(path).and_then(|x| {
+                let errors = x.install(&releases)?;
+                for name in &enable {
+                    x.set_enabled(name, true)?;
+                }
+                Ok(errors)
+            });
+            let _ = sender.send(result);
+            ctx.request_repaint();
+        });
+        self.task = Some((label, receiver));
+    }
+
+    /// Checks whether background download finished.
+    fn poll_task(&mut self) {
+        let Some((_, receiver)) = &self.task else {
+            return;
+        };
+        let Ok(result) = receiver.try_recv() else {
+            return;
+        };
+        match result {
+            Ok(errors) => {
+                self.errors.extend(errors.iter().map(|x| x.to_string()))
+            }
+            Err(e) => self.errors.push(e.to_string()),
+        }
+        self.task = None;
+        self.reload();
+    }
+}
+
+impl Gui {
+    pub(super) fn draw_instances(&mut self, ui: &mut egui::Ui) {
+        let ctx = ui.ctx().clone();
+        let instances = &mut self.instances;
+        instances.poll_task();
+
+        ui.horizontal(|ui| {
+            let text = instances
+                .paths
+                .get(instances.selected)
+                .map(|x| x.display().to_string());
+            let selected = text.is_some();
+            egui::ComboBox::from_label("instance")
+                .selected_text(text.unwrap_or_default())
+                .show_ui(ui, |ui| {
+                    for (i, path) in instances.paths.iter().enumerate() {
+                        let text = path.display().to_string();
+                        if ui
+                            .selectable_value(&mut instances.selected, i, text)
+                            .clicked()
+                        {
+                            instances.loaded = None;
+                        }
+                    }
+                });
+            if selected && ui.button("reload").clicked() {
+                instances.reload();
+            }
+            if selected && ui.button("forget").clicked() {
+                instances.paths.remove(instances.selected);
+                instances.selected = 0;
+                instances.reload();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut instances.new_path)
+                    .hint_text("path to factorio write-data directory"),
+            );
+            if ui.button("add").clicked() {
+                let path = PathBuf::from(instances.new_path.trim());
+                match Instance::open(&path) {
+                    Ok(_) => {
+                        instances.paths.push(path);
+                        instances.selected = instances.paths.len() - 1;
+                        instances.reload();
+                        instances.new_path.clear();
+                    }
+                    Err(e) => instances.errors.push(e.to_string()),
+                }
+            }
+        });
+
+        if !instances.errors.is_empty() {
+            ui.group(|ui| {
+                for e in &instances.errors {
+                    ui.colored_label(Color32::LIGHT_RED, e);
+                }
+                if ui.button("clear errors").clicked() {
+                    instances.errors.clear();
+                }
+            });
+        }
+        ui.separator();
+
+        let Some(path) = instances.paths.get(instances.selected) else {
+            ui.label("add a factorio instance to manage its mods");
+            return;
+        };
+        let loaded = instances
+            .loaded
+            .get_or_insert_with(|| Loaded::load(path, &self.mods).map(Rc::new));
+        // shared, so actions below can borrow `instances` mutably
+        let loaded = match loaded {
+            Ok(x) => Rc::clone(x),
+            Err(e) => {
+                ui.colored_label(Color32::LIGHT_RED, e.to_string());
+                if ui.button("retry").clicked() {
+                    instances.reload();
+                }
+                return;
+            }
+        };
+        let Loaded {
+            instance,
+            installed,
+            updates,
+        } = &*loaded;
+        if let Some((label, _)) = &instances.task {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(label);
+            });
+        } else {
+            ui.horizontal(|ui| {
+                if let Some(name) = &self.selected_mod {
+                    let deps_of = |name: &str| {
+                        let m = self.mods.get(name)?;
+                        let release = m.latest_release()?;
+                        Some(release.info_json.dependencies.as_slice())
+                    };
+                    let missing =
+                        missing_mods(installed, [name.as_str()], deps_of);
+                    let disabled =
+                        disabled_mods(installed, [name.as_str()], deps_of);
+                    let text = match disabled.is_empty() {
+                        true => format!(
+                            "install {name} with dependencies ({} mods)",
+                            missing.len()
+                        ),
+                        false => format!(
+                            "install {name} with dependencies ({} mods, {} \
+                             to enable)",
+                            missing.len(),
+                            disabled.len()
+                        ),
+                    };
+                    let button = egui::Button::new(text);
+                    let enabled = !missing.is_empty() || !disabled.is_empty();
+                    if ui.add_enabled(enabled, button).clicked() {
+                        let releases = missing
+                            .iter()
+                            .filter_map(|&x| {
+                                let m = self.mods.get(x)?;
+                                Some((x.into(), m.latest_release()?.clone()))
+                            })
+                            .collect();
+                        instances.spawn_install(
+                            &ctx,
+                            format!("installing {name}"),
+                            releases,
+                            disabled.iter().map(|&x| x.into()).collect(),
+                        );
+                    }
+                }
+                let button = egui::Button::new(format!(
+                    "update all ({})",
+                    updates.len()
+                ));
+                if ui.add_enabled(!updates.is_empty(), button).clicked() {
+                    instances.spawn_install(
+                        &ctx,
+                        "updating mods".into(),
+                        updates.clone(),
+                        vec![],
+                    );
+                }
+            });
+        }
+        ui.separator();
+
+        // mod to select after drawing, as the list borrows `self`
+        let mut jump_to = None;
+        let busy = instances.task.is_some();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("installed").striped(true).show(ui, |ui| {
+                for m in installed {
+                    let mut enabled = m.enabled;
+                    let checkbox = egui::Checkbox::without_text(&mut enabled);
+                    if ui
+                        .add_enabled(!busy && m.name != "base", checkbox)
+                        .changed()
+                    {
+                        instances
+                            .finish(instance.set_enabled(&m.name, enabled));
+                    }
+                    if self.mods.contains_key(&m.name) {
+                        if ui.link(&m.name).clicked() {
+                            jump_to = Some(m.name.clone());
+                        }
+                    } else {
+                        ui.label(&m.name);
+                    }
+                    ui.label(match (&m.version, &m.path) {
+                        (Some(version), _) => version.to_string(),
+                        (None, None) => "built-in".into(),
+                        (None, Some(_)) => "unknown".into(),
+                    });
+                    match updates.get(&m.name) {
+                        Some(release) => {
+                            let button = egui::Button::new(format!(
+                                "update to {}",
+                                release.version
+                            ));
+                            if ui.add_enabled(!busy, button).clicked() {
+                                instances.spawn_install(
+                                    &ctx,
+                                    format!("updating {}", m.name),
+                                    [(m.name.clone(), release.clone())].into(),
+                                    vec![],
+                                );
+                            }
+                        }
+                        None => {
+                            ui.label("");
+                        }
+                    }
+                    if m.path.is_some() {
+                        if instances.confirm_remove.as_ref() == Some(&m.name) {
+                            let button = egui::Button::new("really remove");
+                            if ui.add_enabled(!busy, button).clicked() {
+                                instances.confirm_remove = None;
+                                instances.finish(instance.remove_mod(&m.name));
+                            }
+                        } else if ui
+                            .add_enabled(!busy, egui::Button::new("remove"))
+                            .clicked()
+                        {
+                            instances.confirm_remove = Some(m.name.clone());
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        if let Some(name) = jump_to {
+            self.jump_to(name);
+            ui.ctx().request_repaint();
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! Rendering of markdown used in mod descriptions.
+//!
+//! Only images hosted on the mod portal's assets host are loaded, others are
+//! shown as links.
+
+use eframe::egui::{self, OpenUrl, RichText, Sense};
+use factorio_crater::cache::images::ASSETS_HOST;
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+const INDENT: f32 = 16.0;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Style {
+    strong: bool,
+    emphasis: bool,
+    strikethrough: bool,
+    code: bool,
+}
+
+#[derive(Debug)]
+enum Inline {
+    Text {
+        text: String,
+        style: Style,
+        link: Option<String>,
+    },
+    Image {
+        url: String,
+        alt: String,
+        link: Option<String>,
+    },
+    LineBreak,
+}
+
+#[derive(Debug)]
+enum Block {
+    Text {
+        inlines: Vec<Inline>,
+        heading: Option<HeadingLevel>,
+        /// nesting of lists and quotes
+        indent: usize,
+        /// bullet or number of list item
+        marker: Option<String>,
+    },
+    Code(String),
+    Rule,
+}
+
+/// Parsed markdown, ready to be drawn.
+#[derive(Debug, Default)]
+pub struct Markdown {
+    blocks: Vec<Block>,
+}
+
+/// Makes links relative to the mod portal absolute.
+fn portal_url(url: &str) -> String {
+    match url.starts_with('/') {
+        true => format!("https://mods.factorio.com{url}"),
+        false => url.into(),
+    }
+}
+
+/// Returns url of image, if it's hosted on the assets host.
+fn asset_url(url: &str) -> Option<String> {
+    if url.starts_with(&format!("{ASSETS_HOST}/")) {
+        Some(url.into())
+    } else if url.starts_with("/assets/") {
+        Some(format!("{ASSETS_HOST}{url}"))
+    } else {
+        None
+    }
+}
+
+#[derive(Default)]
+struct MarkdownParser {
+    blocks: Vec<Block>,
+    inlines: Vec<Inline>,
+    style: Style,
+    link: Option<String>,
+    /// url and alt text of image being parsed
+    image: Option<(String, String)>,
+    heading: Option<HeadingLevel>,
+    /// next number of each nested list, `None` for unordered ones
+    lists: Vec<Option<u64>>,
+    quotes: usize,
+    marker: Option<String>,
+    code_block: Option<String>,
+}
+
+impl MarkdownParser {
+    fn flush(&mut self) {
+        if self.inlines.is_empty() {
+            return;
+        }
+        self.blocks.push(Block::Text {
+            inlines: std::mem::take(&mut self.inlines),
+            heading: self.heading,
+            indent: self.lists.len() + self.quotes,
+            marker: self.marker.take(),
+        });
+    }
+
+    fn text(&mut self, text: &str, style: Style) {
+        if let Some(code) = &mut self.code_block {
+            code.push_str(text);
+        } else if let Some((_, alt)) = &mut self.image {
+            alt.push_str(text);
+        } else {
+            self.inlines.push(Inline::Text {
+                text: text.into(),
+                style,
+                link: self.link.clone(),
+            });
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                self.flush();
+                self.heading = Some(level);
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                self.flush();
+                self.heading = None;
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                self.flush();
+                self.quotes += 1;
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                self.flush();
+                self.quotes -= 1;
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.flush();
+                self.code_block = Some(String::new());
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                let code = self.code_block.take().unwrap_or_default();
+                self.blocks.push(Block::Code(code.trim_end().into()));
+            }
+            Event::Start(Tag::List(start)) => {
+                self.flush();
+                self.lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                self.flush();
+                self.lists.pop();
+            }
+            Event::Start(Tag::Item) => {
+                self.flush();
+                self.marker = Some(match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}.", *n - 1)
+                    }
+                    _ => "•".into(),
+                });
+            }
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Item
+                | TagEnd::TableHead
+                | TagEnd::TableRow,
+            ) => self.flush(),
+            Event::End(TagEnd::TableCell) => self.text(" | ", Style::default()),
+            Event::Start(Tag::Emphasis) => self.style.emphasis = true,
+            Event::End(TagEnd::Emphasis) => self.style.emphasis = false,
+            Event::Start(Tag::Strong) => self.style.strong = true,
+            Event::End(TagEnd::Strong) => self.style.strong = false,
+            Event::Start(Tag::Strikethrough) => self.style.strikethrough = true,
+            Event::End(TagEnd::Strikethrough) => {
+                self.style.strikethrough = false
+            }
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                self.link = Some(portal_url(&dest_url));
+            }
+            Event::End(TagEnd::Link) => self.link = None,
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                self.image = Some((dest_url.to_string(), String::new()));
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((url, alt)) = self.image.take() {
+                    self.inlines.push(Inline::Image {
+                        url,
+                        alt,
+                        link: self.link.clone(),
+                    });
+                }
+            }
+            Event::Text(text) => self.text(&text, self.style),
+            Event::Code(text) => self.text(
+                &text,
+                Style {
+                    code: true,
+                    ..self.style
+                },
+            ),
+            Event::SoftBreak => self.text(" ", self.style),
+            Event::HardBreak => self.inlines.push(Inline::LineBreak),
+            Event::Rule => {
+                self.flush();
+                self.blocks.push(Block::Rule);
+            }
+            Event::TaskListMarker(done) => {
+                self.text(if done { "[x] " } else { "[ ] " }, self.style)
+            }
+            // raw html isn't rendered
+            _ => (),
+        }
+    }
+}
+
+impl Markdown {
+    pub fn parse(text: &str) -> Self {
+        let options = Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TABLES
+            | Options::ENABLE_TASKLISTS;
+        let mut parser = MarkdownParser::default();
+        for event in Parser::new_ext(text, options) {
+            parser.event(event);
+        }
+        parser.flush();
+        Markdown {
+            blocks: parser.blocks,
+        }
+    }
+
+    pub fn show(&self, ui: &mut egui::Ui) {
+        for block in &self.blocks {
+            match block {
+                Block::Text {
+                    inlines,
+                    heading,
+                    indent,
+                    marker,
+                } => {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.add_space(*indent as f32 * INDENT);
+                        if let Some(marker) = marker {
+                            ui.label(marker);
+                        }
+                        for inline in inlines {
+                            show_inline(ui, inline, *heading);
+                        }
+                    });
+                }
+                Block::Code(code) => {
+                    egui::Frame::group(ui.style()).show(ui, |ui| {
+                        ui.label(RichText::new(code).code());
+                    });
+                }
+                Block::Rule => {
+                    ui.separator();
+                }
+            }
+        }
+    }
+}
+
+fn show_inline(
+    ui: &mut egui::Ui,
+    inline: &Inline,
+    heading: Option<HeadingLevel>,
+) {
+    match inline {
+        Inline::Text { text, style, link } => {
+            let mut text = RichText::new(text);
+            text = match heading {
+                Some(HeadingLevel::H1) => text.heading(),
+                Some(HeadingLevel::H2) => text.heading().size(18.0),
+                Some(_) => text.strong(),
+                None => text,
+            };
+            if style.strong {
+                text = text.strong();
+            }
+            if style.emphasis {
+                text = text.italics();
+            }
+            if style.strikethrough {
+                text = text.strikethrough();
+            }
+            if style.code {
+                text = text.code();
+            }
+            match link {
+                Some(url) => ui.hyperlink_to(text, url),
+                None => ui.label(text),
+            };
+        }
+        Inline::Image { url, alt, link } => match asset_url(url) {
+            Some(url) => {
+                let image = egui::Image::new(url)
+                    .max_width(ui.available_width())
+                    .alt_text(alt);
+                if let Some(link) = link {
+                    let response = ui.add(image.sense(Sense::click()));
+                    if response.on_hover_text(link).clicked() {
+                        ui.ctx().open_url(OpenUrl::new_tab(link));
+                    }
+                } else {
+                    ui.add(image);
+                }
+            }
+            None => {
+                let label = if alt.is_empty() { url } else { alt };
+                ui.hyperlink_to(label, link.as_ref().unwrap_or(url));
+            }
+        },
+        Inline::LineBreak => ui.end_row(),
+    }
+}
@@ -0,0 +1,418 @@
+mod breakage;
+mod detail;
+mod images;
+mod instances;
+mod markdown;
+mod refresh;
+mod state;
+mod tree;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+    sync::Arc,
+};
+
+use eframe::{
+    App, CreationContext, Frame,
+    egui::{self, Context, Image, Sense, Vec2},
+};
+use factorio_crater::{
+    APP_ID, Error,
+    analysis::Analysis,
+    cache::{Cache, images::asset_url},
+    deserialization::{Category, ModFull},
+    search::{Filters, SearchIndex, SortBy},
+};
+
+use crate::gui::{
+    breakage::Breakage,
+    detail::{DetailTab, Parsed},
+    images::CachedImageLoader,
+    instances::Instances,
+    refresh::Refresh,
+    state::SavedState,
+};
+use serde::{Deserialize, Serialize};
+
+/// What central panel shows.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+enum View {
+    #[default]
+    Details,
+    Installed,
+    Breakage,
+}
+
+struct Gui {
+    mods: BTreeMap<String, ModFull>,
+    index: SearchIndex,
+    /// values offered by filters
+    tags: Vec<String>,
+    factorio_versions: Vec<String>,
+    query: String,
+    filters: Filters,
+    owner: String,
+    sort: SortBy,
+    /// names of mods matching search, in shown order
+    results: Vec<String>,
+    /// mods marked by the user, kept only locally
+    starred: BTreeSet<String>,
+    starred_only: bool,
+    /// search that `results` are for, to only search again when it changes
+    last_search: Option<(String, Filters, SortBy, bool)>,
+    /// mod the list should scroll to
+    scroll_to: Option<String>,
+    selected_mod: Option<String>,
+    selected_image: Option<String>,
+    /// version whose dependencies are shown, latest one if unset
+    selected_release: Option<String>,
+    detail_tab: DetailTab,
+    parsed: Option<Parsed>,
+    /// breakage analysis, run once it's needed, and whether it was run with
+    /// space age
+    analysis: Option<(bool, Analysis)>,
+    /// whether analysis assumes expansion's built-in mods are available
+    space_age: bool,
+    view: View,
+    breakage: Breakage,
+    /// width of mods list, as last drawn
+    mods_panel_width: Option<f32>,
+    instances: Instances,
+    refresh: Option<Refresh>,
+    refresh_errors: Vec<String>,
+}
+
+impl Gui {
+    fn new(ctx: &CreationContext, refresh_all: bool) -> Result<Self, Error> {
+        egui_extras::install_image_loaders(&ctx.egui_ctx);
+        let cache = Cache::open_default()?;
+        // added last, so it's tried before the http loader
+        ctx.egui_ctx
+            .add_bytes_loader(Arc::new(CachedImageLoader::new(
+                cache.images()?,
+            )));
+        let mut gui = Gui {
+            mods: BTreeMap::new(),
+            index: SearchIndex::default(),
+            tags: vec![],
+            factorio_versions: vec![],
+            query: String::new(),
+            filters: Filters::default(),
+            owner: String::new(),
+            sort: SortBy::default(),
+            starred: BTreeSet::new(),
+            starred_only: false,
+            results: vec![],
+            last_search: None,
+            scroll_to: None,
+            selected_mod: None,
+            selected_image: None,
+            selected_release: None,
+            detail_tab: DetailTab::default(),
+            parsed: None,
+            analysis: None,
+            space_age: true,
+            view: View::default(),
+            breakage: Breakage::default(),
+            mods_panel_width: None,
+            instances: Instances::default(),
+            // fills in mods once they're loaded
+            refresh: Some(Refresh::spawn(&ctx.egui_ctx, refresh_all, true)),
+            refresh_errors: vec![],
+        };
+        gui.restore(SavedState::load(ctx.storage));
+        Ok(gui)
+    }
+
+    /// Replaces shown mods, keeping selection and search.
+    fn set_mods(&mut self, mods: Vec<ModFull>, index: SearchIndex) {
+        self.index = index;
+        let tags = self
+            .index
+            .mods()
+            .flat_map(|x| x.tags.iter().cloned())
+            .collect::<BTreeSet<_>>();
+        let factorio_versions = self
+            .index
+            .mods()
+            .filter_map(|x| x.factorio_version.clone())
+            .collect::<BTreeSet<_>>();
+        self.tags = tags.into_iter().collect();
+        self.factorio_versions = factorio_versions.into_iter().rev().collect();
+        self.mods = mods.into_iter().map(|x| (x.name.clone(), x)).collect();
+        self.last_search = None;
+        self.parsed = None;
+        self.analysis = None;
+        // available updates depend on metadata
+        self.instances.reload();
+    }
+
+    /// Searches again, if query, filters, or sort changed since last frame.
+    fn update_results(&mut self, ctx: &Context) {
+        self.filters.owner =
+            Some(self.owner.trim().to_string()).filter(|x| !x.is_empty());
+        let search = (
+            self.query.clone(),
+            self.filters.clone(),
+            self.sort,
+            self.starred_only,
+        );
+        if self.last_search.as_ref() == Some(&search) {
+            return;
+        }
+        let mut hits = self.index.search(&self.query, &self.filters);
+        if self.starred_only {
+            hits.retain(|x| self.starred.contains(&x.m.name));
+        }
+        self.sort.sort(&mut hits);
+        self.results = hits.into_iter().map(|x| x.m.name.clone()).collect();
+        self.last_search = Some(search);
+        // result count was already drawn for old results
+        ctx.request_repaint();
+    }
+
+    fn select(&mut self, name: String) {
+        self.selected_mod = Some(name);
+        self.selected_image = None;
+        self.selected_release = None;
+    }
+
+    /// Selects a mod, and scrolls the list to it, clearing search if it
+    /// doesn't match.
+    fn jump_to(&mut self, name: String) {
+        if !self.results.contains(&name) {
+            self.query.clear();
+            self.filters = Filters::default();
+            self.owner.clear();
+            self.starred_only = false;
+        }
+        self.scroll_to = Some(name.clone());
+        self.select(name);
+    }
+
+    fn draw_filters(&mut self, ui: &mut egui::Ui) {
+        ui.add(
+            egui::TextEdit::singleline(&mut self.query)
+                .hint_text("search")
+                .desired_width(f32::INFINITY),
+        );
+        egui::CollapsingHeader::new("filters").show(ui, |ui| {
+            egui::Grid::new("filters").num_columns(2).show(ui, |ui| {
+                let categories = Category::KNOWN
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>();
+                ui.label("category");
+                combo_box(
+                    ui,
+                    "category",
+                    &mut self.filters.category,
+                    &categories,
+                );
+                ui.end_row();
+
+                ui.label("tag");
+                combo_box(ui, "tag", &mut self.filters.tag, &self.tags);
+                ui.end_row();
+
+                ui.label("factorio version");
+                combo_box(
+                    ui,
+                    "factorio_version",
+                    &mut self.filters.factorio_version,
+                    &self.factorio_versions,
+                );
+                ui.end_row();
+
+                ui.label("deprecated");
+                let deprecated = &mut self.filters.deprecated;
+                egui::ComboBox::from_id_salt("deprecated")
+                    .selected_text(match deprecated {
+                        None => "any",
+                        Some(true) => "yes",
+                        Some(false) => "no",
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(deprecated, None, "any");
+                        ui.selectable_value(deprecated, Some(true), "yes");
+                        ui.selectable_value(deprecated, Some(false), "no");
+                    });
+                ui.end_row();
+
+                ui.label("owner");
+                ui.text_edit_singleline(&mut self.owner);
+                ui.end_row();
+
+                ui.label("starred only");
+                ui.checkbox(&mut self.starred_only, "");
+                ui.end_row();
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.label("sort by");
+            egui::ComboBox::from_id_salt("sort")
+                .selected_text(self.sort.as_str())
+                .show_ui(ui, |ui| {
+                    for &sort in SortBy::ALL {
+                        ui.selectable_value(
+                            &mut self.sort,
+                            sort,
+                            sort.as_str(),
+                        );
+                    }
+                });
+            ui.label(format!("{} mods", self.results.len()));
+        });
+    }
+}
+
+/// Returns analysis of `mods`, running it again if cached one was run with
+/// other `space_age`.
+fn analysis<'a>(
+    analysis: &'a mut Option<(bool, Analysis)>,
+    mods: &BTreeMap<String, ModFull>,
+    space_age: bool,
+) -> &'a Analysis {
+    if analysis.as_ref().is_none_or(|x| x.0 != space_age) {
+        *analysis = Some((space_age, Analysis::new(mods.values(), space_age)));
+    }
+    &analysis.as_ref().unwrap().1
+}
+
+/// Combo box choosing one of `options`, or none of them.
+fn combo_box(
+    ui: &mut egui::Ui,
+    id: &str,
+    value: &mut Option<String>,
+    options: &[String],
+) {
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(value.as_deref().unwrap_or("any"))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(value, None, "any");
+            for x in options {
+                ui.selectable_value(value, Some(x.clone()), x);
+            }
+        });
+}
+
+const SIZE: f32 = 150.0;
+
+/// Toggle marking a mod as starred.
+fn star_button(ui: &mut egui::Ui, starred: &mut BTreeSet<String>, name: &str) {
+    let is_starred = starred.contains(name);
+    let button =
+        egui::Button::new(if is_starred { "★" } else { "☆" }).frame(false);
+    let hover = if is_starred { "unstar" } else { "star" };
+    if ui.add(button).on_hover_text(hover).clicked() {
+        if is_starred {
+            starred.remove(name);
+        } else {
+            starred.insert(name.into());
+        }
+    }
+}
+
+fn draw_mod_list_item(
+    ui: &mut egui::Ui,
+    m: &ModFull,
+    starred: &mut BTreeSet<String>,
+) -> bool {
+    let mut clicked = false;
+    ui.horizontal(|ui| {
+        let (id, image_space) = ui.allocate_space(Vec2::splat(SIZE));
+        if let Some(image) = &m.thumbnail {
+            ui.put(image_space, Image::new(asset_url(image)));
+        };
+        clicked |= ui.interact(image_space, id, Sense::click()).clicked();
+        ui.vertical(|ui| {
+            ui.horizontal(|ui| {
+                star_button(ui, starred, &m.name);
+                clicked |= ui.heading(&m.title).clicked();
+            });
+            ui.label(format!("by {}", m.owner));
+            ui.label(&m.summary);
+        });
+    });
+    clicked
+}
+
+impl App for Gui {
+    fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+        egui::TopBottomPanel::bottom("status")
+            .show(ctx, |ui| self.draw_refresh(ui));
+        egui::TopBottomPanel::top("view").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.view, View::Details, "details");
+                ui.selectable_value(
+                    &mut self.view,
+                    View::Installed,
+                    "installed mods",
+                );
+                ui.selectable_value(&mut self.view, View::Breakage, "breakage");
+            });
+        });
+        let mut mods_panel = egui::SidePanel::left("mods_list");
+        if let Some(width) = self.mods_panel_width {
+            mods_panel = mods_panel.default_width(width);
+        }
+        let response = mods_panel.show(ctx, |ui| {
+            ui.heading("mods");
+            self.draw_filters(ui);
+            self.update_results(ctx);
+            ui.separator();
+            let mut scroll_area = egui::ScrollArea::vertical();
+            if let Some(name) = self.scroll_to.take()
+                && let Some(i) = self.results.iter().position(|x| *x == name)
+            {
+                let row_height = SIZE + ui.spacing().item_spacing.y;
+                scroll_area =
+                    scroll_area.vertical_scroll_offset(i as f32 * row_height);
+            }
+            let mut clicked = None;
+            scroll_area.show_rows(
+                ui,
+                SIZE,
+                self.results.len(),
+                |ui, Range { start, end }| {
+                    for name in &self.results[start..end] {
+                        let m = &self.mods[name];
+                        if draw_mod_list_item(ui, m, &mut self.starred) {
+                            clicked = Some(name.clone());
+                        };
+                        ui.separator();
+                    }
+                },
+            );
+            if let Some(name) = clicked {
+                self.select(name);
+            }
+        });
+        self.mods_panel_width = Some(response.response.rect.width());
+        egui::CentralPanel::default().show(ctx, |ui| match self.view {
+            View::Details => self.draw_details(ui),
+            View::Installed => self.draw_instances(ui),
+            View::Breakage => self.draw_breakage(ui),
+        });
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, &self.saved_state());
+    }
+}
+
+/// Opens the GUI with cached metadata, while refreshing it in the background
+/// (all of it, with `refresh_all` set).
+pub fn run_gui(refresh_all: bool) -> Result<(), Error> {
+    let options = eframe::NativeOptions::default();
+    eframe::run_native(
+        APP_ID,
+        options,
+        Box::new(move |ctx| Ok(Box::new(Gui::new(ctx, refresh_all)?))),
+    )
+    // eframe's error isn't `Send`
+    .map_err(|e| Error::Gui(e.to_string()))
+}
@@ -0,0 +1,162 @@
+//! Refreshing cached metadata in the background, while the GUI shows what's
+//! already cached.
+//!
+//! Cached metadata is loaded on the same thread, so the window opens without
+//! waiting for it.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::{
+    sync::{
+        Arc,
+        mpsc::{self, Receiver, TryRecvError},
+    },
+    thread,
+    time::Duration,
+};
+
+use eframe::egui::{self, Color32};
+use factorio_crater::{
+    Error, cache::Cache, deserialization::ModFull, download::Progress,
+    search::SearchIndex,
+};
+
+use crate::gui::Gui;
+
+type RefreshResult = Result<(Vec<ModFull>, SearchIndex, Vec<Error>), Error>;
+
+/// Loads cached mods, and their search index persisted in the cache, which
+/// is only built in memory if it's missing or can't be opened.
+fn load(cache: &Cache) -> RefreshResult {
+    let (mods, mut errors) = cache.load_mod_list()?;
+    let index = match SearchIndex::exists(cache) {
+        true => match SearchIndex::open(cache) {
+            Ok((index, index_errors)) => {
+                errors.extend(index_errors);
+                index
+            }
+            Err(e) => {
+                errors.push(e);
+                SearchIndex::build(&mods)
+            }
+        },
+        false => SearchIndex::build(&mods),
+    };
+    Ok((mods, index, errors))
+}
+
+pub struct Refresh {
+    progress: Arc<Progress>,
+    /// set while cache is being loaded, before and after downloading
+    loading: Arc<AtomicBool>,
+    /// mods loaded from cache before downloading (if asked for), and after
+    /// it, closed once refresh is done
+    receiver: Receiver<RefreshResult>,
+}
+
+impl Refresh {
+    /// Starts downloading changed metadata (or all of it, with `all` set), and
+    /// loading the updated cache afterwards. With `cached` set, mods already
+    /// in cache are loaded and sent first.
+    pub fn spawn(ctx: &egui::Context, all: bool, cached: bool) -> Self {
+        let progress = Arc::new(Progress::default());
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let loading = Arc::new(AtomicBool::new(cached));
+        let (thread_progress, thread_loading) =
+            (progress.clone(), loading.clone());
+        thread::spawn(move || {
+            let result = (|| {
+                let cache = Cache::open_default()?;
+                // nothing to show before first update
+                if cached && let Ok(x) = load(&cache) {
+                    let _ = sender.send(Ok(x));
+                    ctx.request_repaint();
+                }
+                thread_loading.store(false, Ordering::Relaxed);
+                let (_, mut errors) =
+                    cache.update_with_progress(all, &thread_progress)?;
+                thread_loading.store(true, Ordering::Relaxed);
+                let (mods, index, load_errors) = load(&cache)?;
+                errors.extend(load_errors);
+                Ok((mods, index, errors))
+            })();
+            let _ = sender.send(result);
+            ctx.request_repaint();
+        });
+        Self {
+            progress,
+            loading,
+            receiver,
+        }
+    }
+}
+
+impl Gui {
+    /// Draws progress of running refresh, and loads mods it sends.
+    pub(super) fn draw_refresh(&mut self, ui: &mut egui::Ui) {
+        if let Some(refresh) = &self.refresh {
+            match refresh.receiver.try_recv() {
+                Ok(Ok((mods, index, errors))) => {
+                    self.refresh_errors =
+                        errors.iter().map(|x| x.to_string()).collect();
+                    self.set_mods(mods, index);
+                }
+                Ok(Err(e)) => self.refresh_errors = vec![e.to_string()],
+                Err(TryRecvError::Disconnected) => self.refresh = None,
+                Err(TryRecvError::Empty) => (),
+            }
+        }
+
+        ui.horizontal(|ui| {
+            match &self.refresh {
+                Some(refresh) => {
+                    let done = refresh.progress.done.load(Ordering::Relaxed);
+                    let total = refresh.progress.total.load(Ordering::Relaxed);
+                    if refresh.loading.load(Ordering::Relaxed) {
+                        ui.spinner();
+                        ui.label("loading metadata");
+                    } else if total == 0 {
+                        ui.spinner();
+                        ui.label("downloading mod list");
+                    } else {
+                        let bar =
+                            egui::ProgressBar::new(done as f32 / total as f32)
+                                .text(format!(
+                                    "downloading metadata {done}/{total}"
+                                ));
+                        ui.add(bar);
+                    }
+                    // progress isn't reported through egui
+                    ui.ctx().request_repaint_after(Duration::from_millis(200));
+                }
+                None => {
+                    if ui.button("refresh").clicked() {
+                        self.refresh =
+                            Some(Refresh::spawn(ui.ctx(), false, false));
+                    }
+                }
+            }
+            ui.label(format!("{} mods", self.mods.len()));
+        });
+        if !self.refresh_errors.is_empty() {
+            let title = format!("{} refresh errors", self.refresh_errors.len());
+            egui::CollapsingHeader::new(
+                egui::RichText::new(title).color(Color32::LIGHT_RED),
+            )
+            .id_salt("refresh_errors")
+            .show(ui, |ui| {
+                let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                egui::ScrollArea::vertical().max_height(200.0).show_rows(
+                    ui,
+                    row_height,
+                    self.refresh_errors.len(),
+                    |ui, range| {
+                        for e in &self.refresh_errors[range] {
+                            ui.label(e);
+                        }
+                    },
+                );
+            });
+        }
+    }
+}
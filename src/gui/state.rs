@@ -0,0 +1,82 @@
+//! GUI state saved across sessions.
+
+use std::{collections::BTreeSet, path::PathBuf};
+
+use eframe::Storage;
+use factorio_crater::search::{Filters, SortBy};
+use serde::{Deserialize, Serialize};
+
+use crate::gui::{Gui, View, detail::DetailTab};
+
+/// Bumped when meaning of existing fields changes, so state saved by other
+/// versions isn't misread. Added fields don't need it, as missing ones get
+/// their defaults.
+const STATE_VERSION: u32 = 1;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedState {
+    pub version: u32,
+    pub selected_mod: Option<String>,
+    pub query: String,
+    pub filters: Filters,
+    pub sort: SortBy,
+    pub starred: BTreeSet<String>,
+    pub starred_only: bool,
+    pub mods_panel_width: Option<f32>,
+    pub view: View,
+    pub detail_tab: DetailTab,
+    pub instances: Vec<PathBuf>,
+    pub selected_instance: usize,
+}
+
+impl SavedState {
+    /// Loads saved state, or defaults if there's none or it was saved with
+    /// different [`STATE_VERSION`].
+    pub fn load(storage: Option<&dyn Storage>) -> Self {
+        let state: Option<Self> =
+            storage.and_then(|x| eframe::get_value(x, eframe::APP_KEY));
+        // there's nothing to migrate yet
+        state
+            .filter(|x| x.version == STATE_VERSION)
+            .unwrap_or_default()
+    }
+}
+
+impl Gui {
+    /// Restores state saved by [`Gui::saved_state`].
+    pub(super) fn restore(&mut self, state: SavedState) {
+        self.owner = state.filters.owner.clone().unwrap_or_default();
+        self.query = state.query;
+        self.filters = state.filters;
+        self.sort = state.sort;
+        self.starred = state.starred;
+        self.starred_only = state.starred_only;
+        self.mods_panel_width = state.mods_panel_width;
+        self.view = state.view;
+        self.detail_tab = state.detail_tab;
+        self.instances.paths = state.instances;
+        self.instances.selected = state.selected_instance;
+        if let Some(name) = state.selected_mod {
+            self.scroll_to = Some(name.clone());
+            self.select(name);
+        }
+    }
+
+    pub(super) fn saved_state(&self) -> SavedState {
+        SavedState {
+            version: STATE_VERSION,
+            selected_mod: self.selected_mod.clone(),
+            query: self.query.clone(),
+            filters: self.filters.clone(),
+            sort: self.sort,
+            starred: self.starred.clone(),
+            starred_only: self.starred_only,
+            mods_panel_width: self.mods_panel_width,
+            view: self.view,
+            detail_tab: self.detail_tab,
+            instances: self.instances.paths.clone(),
+            selected_instance: self.instances.selected,
+        }
+    }
+}
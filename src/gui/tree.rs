@@ -0,0 +1,159 @@
+//! Dependency and reverse dependency trees of selected mod, with status of
+//! every mod in them, as `find_broken_mods` sees it.
+
+use eframe::egui::{
+    self, Color32, RichText, collapsing_header::CollapsingState,
+};
+use factorio_crater::{
+    analysis::{Analysis, ModStatus},
+    deserialization::DepPrefix,
+};
+
+use crate::gui::detail::prefix_description;
+
+pub fn status_color(status: ModStatus) -> Color32 {
+    match status {
+        ModStatus::Working => Color32::LIGHT_GREEN,
+        ModStatus::Broken => Color32::LIGHT_RED,
+        ModStatus::Deprecated => Color32::ORANGE,
+        ModStatus::Missing => Color32::GRAY,
+        ModStatus::Incompatible => Color32::YELLOW,
+        ModStatus::BuiltIn => Color32::LIGHT_BLUE,
+        ModStatus::Unresolved => Color32::from_rgb(200, 150, 255),
+    }
+}
+
+/// Which way the tree goes.
+#[derive(Clone, Copy)]
+enum Direction {
+    Dependencies,
+    Dependents,
+}
+
+/// Draws a link to mod coloured by its status, with reasons of its breakage
+/// on hover, and returns whether it was clicked.
+fn mod_link(ui: &mut egui::Ui, analysis: &Analysis, name: &str) -> bool {
+    let status = analysis.report.status(name);
+    let text = RichText::new(name).color(status_color(status));
+    let mut hover = status.to_string();
+    for reason in analysis.report.reasons(name) {
+        hover.push_str(&format!("\n{reason}"));
+    }
+    if status == ModStatus::Missing {
+        ui.label(text).on_hover_text(hover);
+        return false;
+    }
+    ui.link(text).on_hover_text(hover).clicked()
+}
+
+/// Draws a node, whose children are drawn only once it's expanded, so deep
+/// trees cost nothing until opened.
+///
+/// `path` holds the node's ancestors, used to stop at cycles.
+fn draw_node(
+    ui: &mut egui::Ui,
+    analysis: &Analysis,
+    direction: Direction,
+    name: &str,
+    prefix: Option<DepPrefix>,
+    path: &mut Vec<String>,
+    clicked: &mut Option<String>,
+) {
+    let children: Vec<(&str, Option<DepPrefix>)> = match direction {
+        Direction::Dependencies => analysis
+            .report
+            .mods
+            .get(name)
+            .map(|x| x.dependencies.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .map(|x| (x.name.as_str(), Some(x.prefix)))
+            .collect(),
+        Direction::Dependents => analysis
+            .rdeps
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(|x| (x.as_str(), None))
+            .collect(),
+    };
+    let cycle = path.iter().any(|x| x == name);
+    // incompatible mods aren't loaded, so their dependencies don't matter
+    let expandable = !children.is_empty()
+        && !cycle
+        && prefix != Some(DepPrefix::Incompatible);
+    let mut header = |ui: &mut egui::Ui| {
+        if mod_link(ui, analysis, name) {
+            *clicked = Some(name.to_string());
+        }
+        if let Some(prefix) = prefix.filter(|x| *x != DepPrefix::Required) {
+            ui.weak(prefix_description(prefix));
+        }
+        if cycle {
+            ui.weak("(cycle)");
+        }
+    };
+    if !expandable {
+        ui.horizontal(|ui| {
+            ui.add_space(ui.spacing().indent);
+            header(ui);
+        });
+        return;
+    }
+    let id = ui.make_persistent_id((path.as_slice(), name));
+    CollapsingState::load_with_default_open(ui.ctx(), id, false)
+        .show_header(ui, header)
+        .body(|ui| {
+            path.push(name.to_string());
+            for (child, prefix) in children {
+                draw_node(
+                    ui, analysis, direction, child, prefix, path, clicked,
+                );
+            }
+            path.pop();
+        });
+}
+
+/// Draws dependency and reverse dependency trees of a mod, and returns mod
+/// that was clicked.
+pub fn draw_trees(
+    ui: &mut egui::Ui,
+    analysis: &Analysis,
+    name: &str,
+) -> Option<String> {
+    let mut clicked = None;
+    ui.horizontal_wrapped(|ui| {
+        for &status in ModStatus::ALL {
+            ui.colored_label(status_color(status), status.as_str());
+        }
+    });
+    ui.separator();
+    let status = analysis.report.status(name);
+    ui.horizontal(|ui| {
+        ui.label("status:");
+        ui.colored_label(status_color(status), status.as_str());
+    });
+    for reason in analysis.report.reasons(name) {
+        ui.label(reason);
+    }
+
+    for (direction, title) in [
+        (Direction::Dependencies, "dependencies"),
+        (Direction::Dependents, "required by"),
+    ] {
+        ui.separator();
+        ui.heading(title);
+        ui.push_id(title, |ui| {
+            draw_node(
+                ui,
+                analysis,
+                direction,
+                name,
+                None,
+                &mut vec![],
+                &mut clicked,
+            );
+        });
+    }
+    clicked
+}
@@ -0,0 +1,567 @@
+//! Loading mods in headless factorio, one at a time, like rust's crater
+//! builds every crate.
+//!
+//! Each mod gets its own write-data directory, with only the mod and its
+//! required dependencies enabled, so its failures can't come from other mods.
+
+use core::{fmt, sync::atomic::Ordering, time::Duration};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    thread,
+    time::Instant,
+};
+
+use crate::{
+    Error, INTERNAL_MODS,
+    analysis::resolve::{ResolveError, resolve},
+    cache::Cache,
+    deserialization::{Extra, FullInfoJson, ModFull, Release},
+    download::{PlayerCreds, Progress, download_releases},
+    instance::{ModListEntry, ModListJson},
+    internal_mods,
+    version::Version,
+};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How often running factorio is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Result of loading a mod.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// factorio created a map with the mod (and ran benchmark, if asked to)
+    Loaded,
+    /// lua error in `culprit`, which is the tested mod or one of its
+    /// dependencies
+    LuaError { culprit: String, message: String },
+    /// required dependency isn't on the mod portal, or factorio didn't accept
+    /// the one it got
+    MissingDependency(String),
+    /// factorio didn't exit in time
+    Timeout,
+    /// factorio failed without an error we recognize
+    Failed(String),
+    /// releases couldn't be chosen or downloaded, so factorio wasn't run
+    Skipped(String),
+}
+
+impl Outcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Outcome::Loaded => "ok",
+            Outcome::LuaError { .. } => "lua error",
+            Outcome::MissingDependency(_) => "missing dependency",
+            Outcome::Timeout => "timeout",
+            Outcome::Failed(_) => "failed",
+            Outcome::Skipped(_) => "skipped",
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::LuaError { culprit, message } => {
+                write!(f, "lua error in {culprit}: {message}")
+            }
+            Outcome::MissingDependency(x)
+            | Outcome::Failed(x)
+            | Outcome::Skipped(x) => write!(f, "{}: {x}", self.label()),
+            Outcome::Loaded | Outcome::Timeout => f.write_str(self.label()),
+        }
+    }
+}
+
+/// Message of factorio's error log line, like
+/// `12.345 Error ModManager.cpp:1560: message`.
+fn error_message(line: &str) -> Option<&str> {
+    let (_, rest) = line.trim_start().split_once(' ')?;
+    let (_, message) = rest.strip_prefix("Error ")?.split_once(": ")?;
+    Some(message)
+}
+
+fn is_missing_dependency(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("missing required dependency")
+        || message.contains("unsatisfied dependenc")
+        || message.contains("dependency") && message.contains("not satisfied")
+}
+
+/// Mod in which lua error happened: the one factorio says it failed to load,
+/// or else the first one (but not a built-in one) whose file is in the
+/// message, like `__mod-name__/data.lua:3: ...`.
+fn culprit(message: &str) -> Option<String> {
+    if let Some((_, rest)) = message.split_once("Failed to load mod \"") {
+        return rest.split_once('"').map(|x| x.0.into());
+    }
+    let mut found = Vec::new();
+    let mut rest = message;
+    while let Some((_, after)) = rest.split_once("__") {
+        match after.split_once("__/") {
+            Some((name, after))
+                if !name.is_empty() && !name.contains(char::is_whitespace) =>
+            {
+                found.push(name);
+                rest = after;
+            }
+            _ => rest = after,
+        }
+    }
+    let built_in = |x: &&str| *x == "core" || INTERNAL_MODS.contains(x);
+    let first = found.first().copied();
+    found
+        .into_iter()
+        .find(|x| !built_in(x))
+        .or(first)
+        .map(Into::into)
+}
+
+/// Classifies run of factorio from its log, and exit status (`None` if it
+/// was killed after timeout).
+pub fn classify(log: &str, status: Option<ExitStatus>) -> Outcome {
+    let Some(status) = status else {
+        return Outcome::Timeout;
+    };
+    let errors = log.lines().filter_map(error_message).collect::<Vec<_>>();
+    for message in &errors {
+        if is_missing_dependency(message) {
+            return Outcome::MissingDependency(message.to_string());
+        }
+        if let Some(culprit) = culprit(message) {
+            return Outcome::LuaError {
+                culprit,
+                message: message.to_string(),
+            };
+        }
+    }
+    match (status.success(), errors.last()) {
+        (true, _) => Outcome::Loaded,
+        (false, Some(message)) => Outcome::Failed(message.to_string()),
+        (false, None) => {
+            Outcome::Failed(format!("factorio exited with {status}"))
+        }
+    }
+}
+
+/// Headless factorio, and how to run it.
+#[derive(Debug, Clone)]
+pub struct Headless {
+    /// factorio executable, like `factorio/bin/x64/factorio`
+    pub bin: PathBuf,
+    pub timeout: Duration,
+    /// ticks to run with `--benchmark` once map is created, or `None` to only
+    /// create it
+    pub benchmark_ticks: Option<u32>,
+    pub space_age: bool,
+}
+
+impl Headless {
+    pub fn new(bin: impl Into<PathBuf>) -> Self {
+        Self {
+            bin: bin.into(),
+            timeout: DEFAULT_TIMEOUT,
+            benchmark_ticks: None,
+            space_age: true,
+        }
+    }
+
+    /// Version of factorio, from output of `--version`.
+    pub fn version(&self) -> Result<Version, Error> {
+        let output = Command::new(&self.bin)
+            .arg("--version")
+            .output()
+            .map_err(|e| Error::Instance {
+                path: self.bin.clone(),
+                message: format!("couldn't run factorio: {e}"),
+            })?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| {
+                let version = line.strip_prefix("Version: ")?;
+                version.split_whitespace().next()?.parse().ok()
+            })
+            .ok_or_else(|| Error::Instance {
+                path: self.bin.clone(),
+                message: "couldn't read factorio version".into(),
+            })
+    }
+
+    /// Creates empty write-data directory `dir`, with config pointing
+    /// factorio at it, and `mods` folder holding given mod files, which
+    /// `mod-list.json` enables together with built-in mods.
+    fn prepare(
+        &self,
+        dir: &Path,
+        files: &[(&str, PathBuf)],
+        factorio_version: Version,
+    ) -> Result<PathBuf, Error> {
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+        fs::create_dir_all(dir.join("mods"))?;
+        fs::create_dir_all(dir.join("saves"))?;
+        let dir = fs::canonicalize(dir)?;
+        fs::write(
+            dir.join("config.ini"),
+            format!(
+                "[path]\nread-data=__PATH__executable__/../../data\n\
+                write-data={}\n",
+                dir.display()
+            ),
+        )?;
+
+        let internal = internal_mods(factorio_version, self.space_age);
+        let mut mods = INTERNAL_MODS
+            .iter()
+            .map(|&name| (name, internal.contains(&name)))
+            .collect::<Vec<_>>();
+        for (name, file) in files {
+            mods.push((name, true));
+            let Some(file_name) = file.file_name() else {
+                continue;
+            };
+            let link = dir.join("mods").join(file_name);
+            // mod files are only read, so they don't have to be copied
+            if fs::hard_link(file, &link).is_err() {
+                fs::copy(file, &link)?;
+            }
+        }
+        let mod_list = ModListJson {
+            mods: mods
+                .into_iter()
+                .map(|(name, enabled)| ModListEntry {
+                    name: name.into(),
+                    enabled,
+                    extra: Extra::new(),
+                })
+                .collect(),
+        };
+        let json = simd_json::to_string_pretty(&mod_list).map_err(|e| {
+            Error::Instance {
+                path: dir.clone(),
+                message: e.to_string(),
+            }
+        })?;
+        fs::write(dir.join("mods").join("mod-list.json"), json)?;
+        Ok(dir)
+    }
+
+    /// Runs factorio with write-data directory `dir` and given arguments,
+    /// writing its output into file `log` in `dir`, and killing it after
+    /// timeout.
+    ///
+    /// Returns its log, and exit status, or `None` if it was killed.
+    fn launch(
+        &self,
+        dir: &Path,
+        args: &[&str],
+        log: &str,
+    ) -> Result<(String, Option<ExitStatus>), Error> {
+        let log = dir.join(log);
+        let file = File::create(&log)?;
+        let mut child = Command::new(&self.bin)
+            .arg("--config")
+            .arg(dir.join("config.ini"))
+            .arg("--mod-directory")
+            .arg(dir.join("mods"))
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(file.try_clone()?)
+            .stderr(file)
+            .spawn()?;
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break Some(status);
+            }
+            if start.elapsed() >= self.timeout {
+                child.kill()?;
+                child.wait()?;
+                break None;
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        let log = String::from_utf8_lossy(&fs::read(log)?).into_owned();
+        Ok((log, status))
+    }
+
+    /// Loads given mods, as `(name, file)`, in fresh write-data directory
+    /// `dir`, by creating a map with them, and running benchmark on it if
+    /// `benchmark_ticks` is set.
+    ///
+    /// Logs of factorio are kept in `dir`.
+    pub fn load(
+        &self,
+        dir: &Path,
+        files: &[(&str, PathBuf)],
+        factorio_version: Version,
+    ) -> Result<Outcome, Error> {
+        let dir = self.prepare(dir, files, factorio_version)?;
+        let save = dir.join("saves").join("crater.zip");
+        let save = save.to_string_lossy();
+        let (log, status) =
+            self.launch(&dir, &["--create", &save], "create.log")?;
+        match (classify(&log, status), self.benchmark_ticks) {
+            (Outcome::Loaded, Some(ticks)) => {
+                let ticks = ticks.to_string();
+                let args = ["--benchmark", &save, "--benchmark-ticks", &ticks];
+                let (log, status) =
+                    self.launch(&dir, &args, "benchmark.log")?;
+                Ok(classify(&log, status))
+            }
+            (outcome, _) => Ok(outcome),
+        }
+    }
+}
+
+/// Whether mod has a release for given factorio version.
+fn has_release_for(m: &ModFull, factorio_version: Version) -> bool {
+    m.releases.iter().any(|x| {
+        x.info_json
+            .factorio_version
+            .parse::<Version>()
+            .is_ok_and(|x| x.factorio_version() == factorio_version)
+    })
+}
+
+/// Loads each of given mods (or every cached one with a release for the
+/// factorio version), with releases chosen by [`resolve`], in its own
+/// directory inside `work_dir`, passing outcomes to `on_outcome` as they
+/// come.
+///
+/// Mod files that aren't in cache yet are downloaded there, with credentials
+/// of player logged in `player_data_dir`. `progress` counts downloaded files,
+/// and then loaded mods.
+///
+/// Returns outcomes of all mods, and errors of mods whose metadata or files
+/// couldn't be loaded. Mods for which factorio couldn't be run are `Failed`,
+/// without stopping the run.
+pub fn crater_run(
+    headless: &Headless,
+    cache: &Cache,
+    mods: Option<&[String]>,
+    work_dir: &Path,
+    player_data_dir: &Path,
+    progress: &Progress,
+    mut on_outcome: impl FnMut(&str, &Outcome),
+) -> Result<(BTreeMap<String, Outcome>, Vec<Error>), Error> {
+    let factorio_version = headless.version()?.factorio_version();
+    let (mod_list, mut errors) = cache.load_mod_list()?;
+    let by_name: BTreeMap<&str, &ModFull> =
+        mod_list.iter().map(|x| (x.name.as_str(), x)).collect();
+    let releases_of =
+        |name: &str| by_name.get(name).map(|x| x.releases.as_slice());
+    let names: Vec<&str> = match mods {
+        Some(mods) => mods.iter().map(|x| x.as_str()).collect(),
+        None => mod_list
+            .iter()
+            .filter(|x| has_release_for(x, factorio_version))
+            .map(|x| x.name.as_str())
+            .collect(),
+    };
+
+    let mut outcomes = BTreeMap::new();
+    let mut chosen = BTreeMap::new();
+    for name in names {
+        let result =
+            resolve(&[name], factorio_version, headless.space_age, releases_of);
+        let outcome = match result {
+            Ok(releases) => {
+                chosen.insert(name, releases);
+                continue;
+            }
+            Err(ResolveError::Missing(dep)) if dep != name => {
+                Outcome::MissingDependency(format!("unknown mod {dep}"))
+            }
+            Err(e) => Outcome::Skipped(e.to_string()),
+        };
+        on_outcome(name, &outcome);
+        outcomes.insert(name.to_string(), outcome);
+    }
+
+    let zips = cache.zips_dir();
+    fs::create_dir_all(&zips)?;
+    // different mods can depend on different releases of the same mod, so
+    // they're named by their files
+    let missing: BTreeMap<&str, Option<&Release<FullInfoJson>>> = chosen
+        .values()
+        .flat_map(|x| x.values())
+        .filter(|x| !zips.join(&x.file_name).is_file())
+        .map(|x| (x.file_name.as_str(), Some(*x)))
+        .collect();
+    if !missing.is_empty() {
+        match PlayerCreds::load(player_data_dir) {
+            Ok(creds) => {
+                let files = missing.keys().copied();
+                errors.extend(download_releases(
+                    &creds, &zips, files, &missing, progress,
+                )?);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    progress.done.store(0, Ordering::Relaxed);
+    progress.total.store(chosen.len(), Ordering::Relaxed);
+    for (name, releases) in chosen {
+        let files = releases
+            .iter()
+            .map(|(name, x)| (name.as_str(), zips.join(&x.file_name)))
+            .collect::<Vec<_>>();
+        let outcome = match files.iter().find(|(_, x)| !x.is_file()) {
+            Some((name, _)) => {
+                Outcome::Skipped(format!("{name} isn't downloaded"))
+            }
+            None => headless
+                .load(&work_dir.join(name), &files, factorio_version)
+                .unwrap_or_else(|e| Outcome::Failed(e.to_string())),
+        };
+        progress.done.fetch_add(1, Ordering::Relaxed);
+        on_outcome(name, &outcome);
+        outcomes.insert(name.to_string(), outcome);
+    }
+    Ok((outcomes, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_timeout() {
+        let log = "   0.030 Loading mod a 1.0.0 (data.lua)\n";
+        assert_eq!(classify(log, None), Outcome::Timeout);
+    }
+
+    // building exit statuses, and running the shell stub, only work on unix
+    #[cfg(unix)]
+    mod unix {
+        use std::os::unix::{fs::PermissionsExt, process::ExitStatusExt};
+
+        use super::*;
+
+        const STUB: &str =
+            concat!(env!("CARGO_MANIFEST_DIR"), "/factorio-stub.sh");
+
+        /// Exit status of process that exited with `code`.
+        fn exited(code: i32) -> Option<ExitStatus> {
+            Some(ExitStatus::from_raw(code << 8))
+        }
+
+        #[test]
+        fn classify_loaded() {
+            let log = "   0.030 Loading mod a 1.0.0 (data.lua)\n\
+                0.050 Checksum for core: 1234567890\n\
+                0.070 Goodbye\n";
+            assert_eq!(classify(log, exited(0)), Outcome::Loaded);
+        }
+
+        #[test]
+        fn classify_lua_error() {
+            let log = "   0.030 Loading mod b 1.0.0 (data.lua)\n   0.040 Error \
+                ModManager.cpp:1560: Failed to load mod \"b\": __b__/data.lua:3: \
+                attempt to index global 'stub' (a nil value)\n\
+                stack traceback:\n";
+            assert_eq!(
+                classify(log, exited(1)),
+                Outcome::LuaError {
+                    culprit: "b".into(),
+                    message: "Failed to load mod \"b\": __b__/data.lua:3: \
+                        attempt to index global 'stub' (a nil value)"
+                        .into(),
+                }
+            );
+
+            // without mod factorio failed to load, the first one in traceback
+            let log = "   1.000 Error ModManager.cpp:1560: __base__/data.lua:1: \
+                __c__/prototypes.lua:7: bad\n";
+            let Outcome::LuaError { culprit, .. } = classify(log, exited(1))
+            else {
+                panic!("not a lua error");
+            };
+            assert_eq!(culprit, "c");
+        }
+
+        #[test]
+        fn classify_missing_dependency() {
+            let log = "   0.020 Error Util.cpp:83: Failed to load mods: Mod a \
+                (1.0.0) is missing required dependency stub-dependency\n";
+            assert_eq!(
+                classify(log, exited(1)),
+                Outcome::MissingDependency(
+                    "Failed to load mods: Mod a (1.0.0) is missing required \
+                    dependency stub-dependency"
+                        .into()
+                )
+            );
+        }
+
+        #[test]
+        fn classify_failure() {
+            let log = "   0.030 Loading mod a 1.0.0 (data.lua)\n";
+            assert_eq!(
+                classify(log, exited(1)),
+                Outcome::Failed(format!(
+                    "factorio exited with {}",
+                    exited(1).unwrap()
+                ))
+            );
+        }
+
+        /// Runs `Headless::load` of mods `a` and `b` against the stub, with given
+        /// environment set for it.
+        fn load_with_stub(dir: &Path, env: &str) -> Outcome {
+            // stub reads what to fail from environment, which is set by a wrapper
+            // so tests don't change it for each other
+            fs::create_dir_all(dir).unwrap();
+            let bin = dir.join("factorio.sh");
+            fs::write(&bin, format!("#!/bin/sh\n{env} exec {STUB:?} \"$@\"\n"))
+                .unwrap();
+            fs::set_permissions(&bin, fs::Permissions::from_mode(0o755))
+                .unwrap();
+            let files = ["a", "b"].map(|name| {
+                let file = dir.join(format!("{name}_1.0.0.zip"));
+                File::create(&file).unwrap();
+                (name, file)
+            });
+            let headless = Headless {
+                timeout: Duration::from_secs(1),
+                ..Headless::new(bin)
+            };
+            headless
+                .load(&dir.join("run"), &files, Version::new(2, 0, 0))
+                .unwrap()
+        }
+
+        // one test, so that no other test starts a process while wrappers are
+        // being written, which could fail running them with "text file busy"
+        #[test]
+        fn load_with_stub_outcomes() {
+            let dir = std::env::temp_dir().join(format!(
+                "factorio-crater-headless-{}",
+                std::process::id()
+            ));
+            let outcome =
+                |name: &str, env: &str| load_with_stub(&dir.join(name), env);
+
+            assert_eq!(outcome("loaded", ""), Outcome::Loaded);
+            let lua_error = outcome("lua_error", "STUB_LUA_ERROR=b");
+            let Outcome::LuaError { culprit, .. } = &lua_error else {
+                panic!("not a lua error: {lua_error:?}");
+            };
+            assert_eq!(culprit, "b");
+            let missing = outcome("missing_dep", "STUB_MISSING_DEP=a");
+            let Outcome::MissingDependency(message) = &missing else {
+                panic!("not a missing dependency: {missing:?}");
+            };
+            assert!(message.contains("stub-dependency"), "{message}");
+            assert_eq!(outcome("hang", "STUB_HANG=a"), Outcome::Timeout);
+
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+}
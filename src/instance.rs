@@ -0,0 +1,294 @@
+//! Mods installed in a factorio instance.
+//!
+//! Instance is a factorio write-data directory, with `mods` folder holding
+//! mod files and `mods/mod-list.json` saying which of them are enabled.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, INTERNAL_MODS,
+    analysis::conflicts::required_closure,
+    deserialization::{Dep, Extra, Release},
+    download::{Progress, download_mods},
+    version::Version,
+};
+
+/// Contents of `mod-list.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModListJson {
+    pub mods: Vec<ModListEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModListEntry {
+    pub name: String,
+    pub enabled: bool,
+    /// fields we don't use, kept so saving doesn't lose them
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledMod {
+    pub name: String,
+    /// `None` for built-in mods, and for unzipped ones without version in
+    /// their folder's name
+    pub version: Option<Version>,
+    /// zip or folder in `mods`, `None` for built-in mods
+    pub path: Option<PathBuf>,
+    pub enabled: bool,
+}
+
+pub struct Instance {
+    root: PathBuf,
+}
+
+/// Splits name of mod's file or folder into mod name and version.
+fn parse_file_name(file_name: &str) -> Option<(&str, Option<Version>)> {
+    if let Some(stem) = file_name.strip_suffix(".zip") {
+        let (name, version) = stem.rsplit_once('_')?;
+        return Some((name, Some(version.parse().ok()?)));
+    }
+    match file_name.rsplit_once('_') {
+        Some((name, version)) => match version.parse() {
+            Ok(version) => Some((name, Some(version))),
+            Err(_) => Some((file_name, None)),
+        },
+        None => Some((file_name, None)),
+    }
+}
+
+impl Instance {
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, Error> {
+        let root = root.into();
+        if !root.join("mods").is_dir() {
+            return Err(Error::Instance {
+                path: root,
+                message: "missing mods folder".into(),
+            });
+        }
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn mods_dir(&self) -> PathBuf {
+        self.root.join("mods")
+    }
+
+    pub fn mod_list_path(&self) -> PathBuf {
+        self.mods_dir().join("mod-list.json")
+    }
+
+    /// Reads `mod-list.json`, which factorio creates on first launch, so it's
+    /// empty if it doesn't exist yet.
+    pub fn load_mod_list(&self) -> Result<ModListJson, Error> {
+        let path = self.mod_list_path();
+        let mut bytes = match fs::read(&path) {
+            Ok(x) => x,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(ModListJson::default());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        simd_json::from_slice(&mut bytes).map_err(|e| Error::Instance {
+            path,
+            message: format!("invalid mod-list.json: {e}"),
+        })
+    }
+
+    pub fn save_mod_list(&self, mod_list: &ModListJson) -> Result<(), Error> {
+        let path = self.mod_list_path();
+        let json = simd_json::to_string_pretty(mod_list).map_err(|e| {
+            Error::Instance {
+                path: path.clone(),
+                message: e.to_string(),
+            }
+        })?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Lists mods in `mods` folder and `mod-list.json`.
+    ///
+    /// Mods not in `mod-list.json` are enabled, as that's what factorio does
+    /// with newly added mods.
+    pub fn installed_mods(&self) -> Result<Vec<InstalledMod>, Error> {
+        let mod_list = self.load_mod_list()?;
+        let enabled: BTreeMap<&str, bool> = mod_list
+            .mods
+            .iter()
+            .map(|x| (x.name.as_str(), x.enabled))
+            .collect();
+        let mut installed = BTreeMap::<String, InstalledMod>::new();
+        for entry in fs::read_dir(self.mods_dir())? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+            if !entry.file_type()?.is_dir() && !file_name.ends_with(".zip") {
+                continue;
+            }
+            let Some((name, version)) = parse_file_name(file_name) else {
+                continue;
+            };
+            // keep the newest of multiple versions, as factorio does
+            if installed.get(name).is_some_and(|x| x.version > version) {
+                continue;
+            }
+            installed.insert(
+                name.into(),
+                InstalledMod {
+                    name: name.into(),
+                    version,
+                    path: Some(entry.path()),
+                    enabled: enabled.get(name).copied().unwrap_or(true),
+                },
+            );
+        }
+        for m in &mod_list.mods {
+            if INTERNAL_MODS.contains(&m.name.as_str()) {
+                installed.insert(
+                    m.name.clone(),
+                    InstalledMod {
+                        name: m.name.clone(),
+                        version: None,
+                        path: None,
+                        enabled: m.enabled,
+                    },
+                );
+            }
+        }
+        Ok(installed.into_values().collect())
+    }
+
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> Result<(), Error> {
+        let mut mod_list = self.load_mod_list()?;
+        match mod_list.mods.iter_mut().find(|x| x.name == name) {
+            Some(entry) => entry.enabled = enabled,
+            None => mod_list.mods.push(ModListEntry {
+                name: name.into(),
+                enabled,
+                extra: Extra::new(),
+            }),
+        }
+        self.save_mod_list(&mod_list)
+    }
+
+    /// Deletes every installed version of a mod, and removes it from
+    /// `mod-list.json`.
+    pub fn remove_mod(&self, name: &str) -> Result<(), Error> {
+        self.remove_files(name, None)?;
+        let mut mod_list = self.load_mod_list()?;
+        mod_list.mods.retain(|x| x.name != name);
+        self.save_mod_list(&mod_list)
+    }
+
+    /// Deletes files and folders of a mod, except for `keep`.
+    fn remove_files(
+        &self,
+        name: &str,
+        keep: Option<&str>,
+    ) -> Result<(), Error> {
+        for entry in fs::read_dir(self.mods_dir())? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+            if Some(file_name) == keep
+                || parse_file_name(file_name).is_none_or(|(x, _)| x != name)
+            {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                fs::remove_dir_all(entry.path())?;
+            } else if file_name.ends_with(".zip") {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Downloads given releases of mods, replaces their other installed
+    /// versions, and enables them.
+    ///
+    /// Returns errors of mods that couldn't be installed.
+    pub fn install<'a, INFO: Sync>(
+        &self,
+        mods: &BTreeMap<&'a str, Option<&'a Release<INFO>>>,
+    ) -> Result<Vec<Error>, Error> {
+        let mut errors = download_mods(
+            &self.root,
+            mods.keys().copied(),
+            mods,
+            &Progress::default(),
+        )?;
+        let failed = errors
+            .iter()
+            .filter_map(|x| match x {
+                Error::Mod { name, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut mod_list = self.load_mod_list()?;
+        for (&name, release) in mods {
+            let Some(release) = release else { continue };
+            if failed.iter().any(|x| x == name) {
+                continue;
+            }
+            if let Err(e) = self.remove_files(name, Some(&release.file_name)) {
+                errors.push(Error::Mod {
+                    name: name.into(),
+                    source: Box::new(e),
+                });
+            }
+            match mod_list.mods.iter_mut().find(|x| x.name == name) {
+                Some(entry) => entry.enabled = true,
+                None => mod_list.mods.push(ModListEntry {
+                    name: name.into(),
+                    enabled: true,
+                    extra: Extra::new(),
+                }),
+            }
+        }
+        self.save_mod_list(&mod_list)?;
+        Ok(errors)
+    }
+}
+
+/// Returns `roots` and mods they transitively require, which aren't installed
+/// or built-in.
+pub fn missing_mods<'a>(
+    installed: &[InstalledMod],
+    roots: impl IntoIterator<Item = &'a str>,
+    deps_of: impl Fn(&str) -> Option<&'a [Dep]>,
+) -> Vec<&'a str> {
+    required_closure(roots, deps_of)
+        .into_iter()
+        .filter(|x| !INTERNAL_MODS.contains(x))
+        .filter(|x| !installed.iter().any(|m| m.name == *x))
+        .collect()
+}
+
+/// Returns `roots` and mods they transitively require, which are installed but
+/// disabled.
+pub fn disabled_mods<'a>(
+    installed: &[InstalledMod],
+    roots: impl IntoIterator<Item = &'a str>,
+    deps_of: impl Fn(&str) -> Option<&'a [Dep]>,
+) -> Vec<&'a str> {
+    required_closure(roots, deps_of)
+        .into_iter()
+        .filter(|x| installed.iter().any(|m| m.name == *x && !m.enabled))
+        .collect()
+}
@@ -0,0 +1,41 @@
+//! Library for analysing metadata of factorio mods from the mod portal, and
+//! managing mods of factorio instances.
+
+pub mod analysis;
+pub mod cache;
+pub mod changelog;
+pub mod deserialization;
+pub mod download;
+mod error;
+pub mod headless;
+pub mod instance;
+#[cfg(feature = "lua")]
+pub mod lua;
+pub mod search;
+pub mod version;
+
+pub use crate::error::Error;
+use crate::version::Version;
+
+/// Mods shipped with the game, which aren't on the mod portal.
+pub const INTERNAL_MODS: &[&str] =
+    &["base", "elevated-rails", "quality", "space-age"];
+pub const SPACE_AGE_VERSION: Version = Version::new(2, 0, 0);
+pub const USER_AGENT: &str = "factorio-crater/0.1.0 (by Shadow0133 aka Aurora)";
+pub const APP_ID: &str = "factorio-crater";
+
+/// Returns built-in mods available in given factorio version.
+///
+/// `elevated-rails` and `quality` are part of space age expansion (and
+/// `space-age` mod requires both of them), so they either all exist, or none
+/// of them do.
+pub fn internal_mods(
+    factorio_version: Version,
+    space_age: bool,
+) -> &'static [&'static str] {
+    if space_age && factorio_version >= SPACE_AGE_VERSION {
+        INTERNAL_MODS
+    } else {
+        &INTERNAL_MODS[..1]
+    }
+}
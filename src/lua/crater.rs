@@ -0,0 +1,261 @@
+//! `crater` table, with helpers implemented in Rust.
+
+use core::cell::OnceCell;
+use std::{collections::BTreeMap, rc::Rc};
+
+use mlua::{AnyUserData, Lua, MetaMethod, Table, UserData, UserDataRef, Value};
+
+use crate::{
+    analysis::{Analysis, resolve::resolve},
+    deserialization::{Dep, ModFull},
+    lua::{Describe, Description, lazy::LazyMods},
+    version::Version,
+};
+
+/// Analysis of all mods, with and without space age, run once a script asks
+/// for it.
+#[derive(Default)]
+struct Analyses([OnceCell<Rc<Analysis>>; 2]);
+
+impl Analyses {
+    fn get(
+        &self,
+        lua: &Lua,
+        mods: &LazyMods,
+        space_age: bool,
+    ) -> mlua::Result<Rc<Analysis>> {
+        if let Some(x) = self.0[space_age as usize].get() {
+            return Ok(x.clone());
+        }
+        let mods = all_mods(lua, mods)?;
+        let analysis =
+            Rc::new(Analysis::new(mods.iter().map(|x| &**x), space_age));
+        Ok(self.0[space_age as usize].get_or_init(|| analysis).clone())
+    }
+}
+
+fn all_mods(
+    lua: &Lua,
+    mods: &LazyMods,
+) -> mlua::Result<Vec<UserDataRef<ModFull>>> {
+    mods.all(lua)?
+        .sequence_values::<AnyUserData>()
+        .map(|x| x?.borrow::<ModFull>())
+        .collect()
+}
+
+/// Accepts `Version` userdata, or a version string.
+fn version_arg(value: Value) -> mlua::Result<Version> {
+    match value {
+        Value::UserData(x) => Ok(*x.borrow::<Version>()?),
+        Value::String(x) => x.to_str()?.parse().map_err(mlua::Error::external),
+        x => Err(mlua::Error::runtime(format!(
+            "expected version, got {}",
+            x.type_name()
+        ))),
+    }
+}
+
+/// Accepts `Dep` userdata, or a dependency string.
+fn dep_arg(value: Value) -> mlua::Result<Dep> {
+    match value {
+        Value::UserData(x) => Ok(x.borrow::<Dep>()?.clone()),
+        Value::String(x) => {
+            Dep::parse(&x.to_str()?).map_err(mlua::Error::external)
+        }
+        x => Err(mlua::Error::runtime(format!(
+            "expected dependency, got {}",
+            x.type_name()
+        ))),
+    }
+}
+
+/// Creates `crater` table, whose functions look up given mods.
+pub fn crater_table(lua: &Lua, mods: Rc<LazyMods>) -> mlua::Result<Table> {
+    let crater = lua.create_table()?;
+    let analyses = Rc::new(Analyses::default());
+
+    let get_mods = mods.clone();
+    crater.set(
+        "get",
+        lua.create_function(move |lua, name: String| get_mods.get(lua, &name))?,
+    )?;
+
+    // returns version, or nil and error message
+    crater.set(
+        "parse_version",
+        lua.create_function(|_, version: String| {
+            Ok(match version.parse::<Version>() {
+                Ok(x) => (Some(x), None),
+                Err(e) => (None, Some(e.to_string())),
+            })
+        })?,
+    )?;
+
+    crater.set(
+        "version_satisfies",
+        lua.create_function(|_, (dep, version): (Value, Value)| {
+            let dep = dep_arg(dep)?;
+            let version = version_arg(version)?;
+            Ok(dep.version.is_none_or(|x| x.matches(version)))
+        })?,
+    )?;
+
+    let (rdeps_mods, rdeps_analyses) = (mods.clone(), analyses.clone());
+    crater.set(
+        "rdeps",
+        lua.create_function(move |lua, name: String| {
+            let analysis = rdeps_analyses.get(lua, &rdeps_mods, true)?;
+            let rdeps = analysis.rdeps.get(&name).into_iter().flatten();
+            Ok(rdeps.cloned().collect::<Vec<_>>())
+        })?,
+    )?;
+
+    // returns table of mod names to versions, or nil and error message
+    let resolve_mods = mods.clone();
+    crater.set(
+        "resolve",
+        lua.create_function(
+            move |lua,
+                  (names, factorio_version, space_age): (
+                Vec<String>,
+                Value,
+                Option<bool>,
+            )| {
+                let factorio_version = version_arg(factorio_version)?;
+                let all = all_mods(lua, &resolve_mods)?;
+                let all: BTreeMap<&str, &ModFull> =
+                    all.iter().map(|x| (x.name.as_str(), &**x)).collect();
+                let releases_of =
+                    |name: &str| all.get(name).map(|x| x.releases.as_slice());
+                let names =
+                    names.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+                match resolve(
+                    &names,
+                    factorio_version,
+                    space_age.unwrap_or(true),
+                    releases_of,
+                ) {
+                    Ok(chosen) => {
+                        let table = lua.create_table()?;
+                        for (name, release) in chosen {
+                            table.set(name, release.version.as_str())?;
+                        }
+                        Ok((Some(table), None))
+                    }
+                    Err(e) => Ok((None, Some(e.to_string()))),
+                }
+            },
+        )?,
+    )?;
+
+    crater.set(
+        "broken_report",
+        lua.create_function(move |lua, space_age: Option<bool>| {
+            let space_age = space_age.unwrap_or(true);
+            Ok(Report(analyses.get(lua, &mods, space_age)?))
+        })?,
+    )?;
+
+    Ok(crater)
+}
+
+impl UserData for Version {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("major", |_, this| Ok(this.major));
+        fields.add_field_method_get("minor", |_, this| Ok(this.minor));
+        fields.add_field_method_get("sub", |_, this| Ok(this.sub));
+    }
+
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            Ok(this.to_string())
+        });
+        methods.add_meta_method(
+            MetaMethod::Eq,
+            |_, this, other: UserDataRef<Version>| Ok(*this == *other),
+        );
+        methods.add_meta_method(
+            MetaMethod::Lt,
+            |_, this, other: UserDataRef<Version>| Ok(*this < *other),
+        );
+        methods.add_meta_method(
+            MetaMethod::Le,
+            |_, this, other: UserDataRef<Version>| Ok(*this <= *other),
+        );
+    }
+}
+
+impl Describe for Version {
+    const DESCRIPTION: Description = Description {
+        tostring: true,
+        ..Description::new("Version")
+    };
+}
+
+/// Result of `crater.broken_report()`.
+pub(super) struct Report(Rc<Analysis>);
+
+impl UserData for Report {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("deprecated", |_, this| {
+            Ok(this.0.report.deprecated.iter().cloned().collect::<Vec<_>>())
+        });
+        fields.add_field_method_get("working", |_, this| {
+            Ok(this.0.report.working.iter().cloned().collect::<Vec<_>>())
+        });
+        fields.add_field_method_get("broken", |_, this| {
+            let broken = this.0.report.broken.iter();
+            Ok(broken
+                .map(|(name, m)| (name.clone(), m.broken_deps.clone()))
+                .collect::<BTreeMap<_, _>>())
+        });
+        fields.add_field_method_get("missing_deps", |_, this| {
+            Ok(this.0.report.typod.clone())
+        });
+        fields.add_field_method_get("unresolved", |_, this| {
+            Ok(this.0.report.unresolved.iter().cloned().collect::<Vec<_>>())
+        });
+        fields.add_field_method_get("uninstallable", |_, this| {
+            let uninstallable = this.0.report.uninstallable.iter();
+            Ok(uninstallable
+                .map(|(name, conflicts)| {
+                    let conflicts = conflicts.iter().map(|x| x.to_string());
+                    (name.clone(), conflicts.collect::<Vec<_>>())
+                })
+                .collect::<BTreeMap<_, _>>())
+        });
+    }
+
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("status", |_, this, name: String| {
+            Ok(this.0.report.status(&name).as_str())
+        });
+        methods.add_method("reasons", |_, this, name: String| {
+            Ok(this.0.report.reasons(&name))
+        });
+    }
+}
+
+impl Describe for Report {
+    const DESCRIPTION: Description = Description {
+        docs: &[
+            (
+                "broken",
+                "broken mods, with their deprecated, broken, or unavailable \
+                dependencies",
+            ),
+            (
+                "missing_deps",
+                "mods requiring a mod that doesn't exist, with that mod",
+            ),
+            ("unresolved", "mods in dependency cycles"),
+            (
+                "uninstallable",
+                "working mods whose dependencies are incompatible with each \
+                other",
+            ),
+        ],
+        ..Description::new("BrokenReport")
+    };
+}
@@ -0,0 +1,300 @@
+//! LuaLS definitions of userdata, generated from their `UserData` impls.
+
+use core::{any::type_name, fmt::Write};
+use std::{collections::BTreeSet, fs, path::Path};
+
+use mlua::{AnyUserData, UserData, UserDataFields, UserDataMethods};
+
+use crate::{
+    Error,
+    changelog::{Changelog, ChangelogCategory, ChangelogVersion},
+    deserialization::{
+        Dep, FullInfoJson, Image, LatestRelease, License, Mod, ModFull,
+        Release, ShortInfoJson,
+    },
+    lua::{Describe, Description, crater::Report},
+    version::Version,
+};
+
+/// First line of generated part of definitions file.
+const BEGIN: &str =
+    "-- generated from rust types by `factorio-crater lua-defs`, don't edit\n";
+/// Last line of generated part of definitions file.
+const END: &str = "-- end of generated definitions\n";
+
+/// Fields and methods registered by `UserData` impl.
+#[derive(Default)]
+pub(crate) struct Members {
+    /// names of fields, with rust types of their values
+    pub fields: Vec<(String, &'static str)>,
+    pub methods: Vec<String>,
+}
+
+impl Members {
+    pub fn of<T: UserData>() -> Self {
+        let mut members = Members::default();
+        T::add_fields(&mut members);
+        T::add_methods(&mut members);
+        members
+    }
+}
+
+impl<T> UserDataFields<T> for Members {
+    fn add_field<V>(&mut self, name: impl ToString, _: V) {
+        self.fields.push((name.to_string(), type_name::<V>()));
+    }
+
+    fn add_field_method_get<M, R>(&mut self, name: impl ToString, _: M) {
+        self.fields.push((name.to_string(), type_name::<R>()));
+    }
+
+    fn add_field_method_set<M, A>(&mut self, _: impl ToString, _: M) {}
+
+    fn add_field_function_get<F, R>(&mut self, name: impl ToString, _: F) {
+        self.fields.push((name.to_string(), type_name::<R>()));
+    }
+
+    fn add_field_function_set<F, A>(&mut self, _: impl ToString, _: F) {}
+
+    fn add_meta_field<V>(&mut self, _: impl ToString, _: V) {}
+
+    fn add_meta_field_with<F, R>(&mut self, _: impl ToString, _: F) {}
+}
+
+impl<T> UserDataMethods<T> for Members {
+    fn add_method<M, A, R>(&mut self, name: impl ToString, _: M) {
+        self.methods.push(name.to_string());
+    }
+
+    fn add_method_mut<M, A, R>(&mut self, name: impl ToString, _: M) {
+        self.methods.push(name.to_string());
+    }
+
+    fn add_function<F, A, R>(&mut self, name: impl ToString, _: F) {
+        self.methods.push(name.to_string());
+    }
+
+    fn add_function_mut<F, A, R>(&mut self, name: impl ToString, _: F) {
+        self.methods.push(name.to_string());
+    }
+
+    fn add_meta_method<M, A, R>(&mut self, _: impl ToString, _: M) {}
+
+    fn add_meta_method_mut<M, A, R>(&mut self, _: impl ToString, _: M) {}
+
+    fn add_meta_function<F, A, R>(&mut self, _: impl ToString, _: F) {}
+
+    fn add_meta_function_mut<F, A, R>(&mut self, _: impl ToString, _: F) {}
+}
+
+/// Userdata type given to lua.
+pub(crate) struct Class {
+    pub description: &'static Description,
+    /// name of rust type, as given by `type_name`
+    rust_name: &'static str,
+    pub is: fn(&AnyUserData) -> bool,
+    pub members: fn() -> Members,
+}
+
+fn class<T: Describe>() -> Class {
+    Class {
+        description: &T::DESCRIPTION,
+        rust_name: type_name::<T>(),
+        is: AnyUserData::is::<T>,
+        members: Members::of::<T>,
+    }
+}
+
+/// All userdata types, in order of definitions file.
+pub(crate) fn classes() -> Vec<Class> {
+    vec![
+        class::<ModFull>(),
+        class::<Mod>(),
+        class::<Changelog>(),
+        class::<ChangelogVersion>(),
+        class::<ChangelogCategory>(),
+        class::<Image>(),
+        class::<License>(),
+        class::<Release<FullInfoJson>>(),
+        class::<FullInfoJson>(),
+        class::<LatestRelease>(),
+        class::<ShortInfoJson>(),
+        class::<Dep>(),
+        class::<Version>(),
+        class::<Report>(),
+    ]
+}
+
+/// `Release` from `factorio_crater::deserialization::Release<...>`.
+fn short_name(rust: &str) -> &str {
+    let path = rust.split('<').next().unwrap_or(rust);
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+/// Splits generic arguments at commas that aren't nested in other generics.
+fn split_args(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in args.char_indices() {
+        match c {
+            '<' | '(' => depth += 1,
+            '>' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(args[start..].trim());
+    parts
+}
+
+/// LuaLS type of values of rust type, as named by `type_name`.
+fn lua_type(rust: &str, classes: &[Class]) -> String {
+    let rust = rust.trim().trim_start_matches('&');
+    if let Some(class) = classes.iter().find(|x| x.rust_name == rust) {
+        return class.description.name.into();
+    }
+    let args = match rust.split_once('<') {
+        Some((_, args)) => split_args(args.strip_suffix('>').unwrap_or(args)),
+        None => Vec::new(),
+    };
+    let ty = |x: &str| lua_type(x, classes);
+    match (short_name(rust), args.as_slice()) {
+        ("Option", [x]) => format!("{}?", ty(x)),
+        ("Vec", [x]) => format!("{}[]", ty(x)),
+        ("BTreeMap" | "HashMap", [k, v]) => {
+            format!("table<{}, {}>", ty(k), ty(v))
+        }
+        ("String" | "str", _) => "string".into(),
+        ("bool", _) => "boolean".into(),
+        ("f32" | "f64", _) => "number".into(),
+        (
+            "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32"
+            | "u64" | "usize",
+            _,
+        ) => "integer".into(),
+        ("DepPrefix", _) => "Prefix".into(),
+        // other types aren't defined, which LuaLS points out
+        (name, _) => name.into(),
+    }
+}
+
+/// `---@class` definitions of all userdata types, with their fields.
+fn generate() -> String {
+    let classes = classes();
+    let mut out = String::new();
+    for (i, class) in classes.iter().enumerate() {
+        let name = class.description.name;
+        if i > 0 {
+            out.push('\n');
+        }
+        let _ = writeln!(out, "---@class {name}");
+        for (field, rust) in (class.members)().fields {
+            let _ =
+                write!(out, "---@field {field} {}", lua_type(rust, &classes));
+            let mut docs = class.description.docs.iter();
+            if let Some((_, doc)) = docs.find(|x| x.0 == field) {
+                let _ = write!(out, " {doc}");
+            }
+            out.push('\n');
+        }
+        let _ = writeln!(out, "local {name} = {{}}");
+    }
+    out
+}
+
+/// `(class, method)` for every `function Class:method(` line.
+fn documented_methods(text: &str) -> BTreeSet<(&str, &str)> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.strip_prefix("function ")?;
+            let (class, rest) = line.split_once(':')?;
+            let (method, _) = rest.split_once('(')?;
+            Some((class.trim(), method.trim()))
+        })
+        .collect()
+}
+
+/// Offsets of `BEGIN` line, and start and end of generated text after it.
+fn generated_section(text: &str) -> Option<(usize, usize, usize)> {
+    let begin = text.find(BEGIN)?;
+    let start = begin + BEGIN.len();
+    Some((begin, start, start + text[start..].find(END)?))
+}
+
+/// Updates generated part of lua definitions file, to match `UserData` impls,
+/// or with `check`, only compares them.
+///
+/// Returns problems found: outdated definitions (with `check`), and methods
+/// that aren't documented in hand-written part of the file, or don't exist.
+pub fn update_defs(path: &Path, check: bool) -> Result<Vec<String>, Error> {
+    let text = fs::read_to_string(path)?;
+    let Some((begin, start, end)) = generated_section(&text) else {
+        return Err(Error::Lua(format!(
+            "{} has no generated section, starting with {BEGIN:?}, and \
+            ending with {END:?}",
+            path.display()
+        )));
+    };
+
+    let mut problems = Vec::new();
+    let generated = generate();
+    if text[start..end] != generated {
+        if check {
+            problems.push(format!(
+                "definitions in {} are out of date, run `factorio-crater \
+                lua-defs` to update them",
+                path.display()
+            ));
+        } else {
+            let text = [&text[..start], &generated, &text[end..]].concat();
+            fs::write(path, text)?;
+        }
+    }
+
+    let hand_written = [&text[..begin], &text[end + END.len()..]].concat();
+    let documented = documented_methods(&hand_written);
+    for class in classes() {
+        let name = class.description.name;
+        let methods = (class.members)().methods;
+        for method in &methods {
+            if !documented.contains(&(name, method.as_str())) {
+                problems.push(format!("{name}:{method} isn't documented"));
+            }
+        }
+        for &(_, method) in documented.iter().filter(|x| x.0 == name) {
+            if !methods.iter().any(|x| x == method) {
+                problems.push(format!(
+                    "{name}:{method} is documented, but doesn't exist"
+                ));
+            }
+        }
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/defs.lua");
+
+    #[test]
+    fn committed_defs_are_up_to_date() {
+        let text = fs::read_to_string(DEFS_PATH).unwrap();
+        let (_, start, end) = generated_section(&text).unwrap();
+        assert_eq!(
+            text[start..end],
+            generate(),
+            "run `factorio-crater lua-defs` to update defs.lua"
+        );
+    }
+
+    #[test]
+    fn methods_are_documented() {
+        let problems = update_defs(Path::new(DEFS_PATH), true).unwrap();
+        assert_eq!(problems, Vec::<String>::new());
+    }
+}
@@ -0,0 +1,209 @@
+//! `mods` and `mods_short` globals, loading metadata on first access.
+
+use core::cell::OnceCell;
+use std::{collections::BTreeSet, rc::Rc};
+
+use mlua::{AnyUserData, Function, Lua, MultiValue, Table, Value};
+
+use crate::{Error, cache::Cache};
+
+/// Full metadata of cached mods, each parsed once a script asks for it.
+pub(crate) struct LazyMods {
+    cache: Cache,
+    /// called with errors of mods that couldn't be loaded
+    on_error: Box<dyn Fn(&Error)>,
+    names: BTreeSet<String>,
+    /// userdata of loaded mods, or `false` for mods that couldn't be loaded
+    loaded: Table,
+    /// all mods that could be loaded, in order of names
+    all: OnceCell<Table>,
+}
+
+impl LazyMods {
+    pub fn new(
+        lua: &Lua,
+        cache: &Cache,
+        on_error: impl Fn(&Error) + 'static,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            cache: cache.clone(),
+            on_error: Box::new(on_error),
+            names: cache.cached_mod_names()?,
+            loaded: lua
+                .create_table()
+                .map_err(|e| Error::Lua(e.to_string()))?,
+            all: OnceCell::new(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Names of cached mods, including ones that can't be loaded.
+    pub fn names(&self) -> &BTreeSet<String> {
+        &self.names
+    }
+
+    /// Mod with given name, loading it if it wasn't loaded yet.
+    ///
+    /// Mods that couldn't be loaded are passed to `on_error`, and treated as
+    /// missing.
+    pub fn get(
+        &self,
+        lua: &Lua,
+        name: &str,
+    ) -> mlua::Result<Option<AnyUserData>> {
+        if !self.names.contains(name) {
+            return Ok(None);
+        }
+        match self.loaded.raw_get::<Value>(name)? {
+            Value::UserData(x) => return Ok(Some(x)),
+            Value::Boolean(false) => return Ok(None),
+            _ => (),
+        }
+        match self.cache.load_mod(name) {
+            Ok(m) => {
+                let m = lua.create_userdata(m)?;
+                self.loaded.raw_set(name, &m)?;
+                Ok(Some(m))
+            }
+            Err(e) => {
+                (self.on_error)(&e);
+                self.loaded.raw_set(name, false)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Sequence of all mods that could be loaded, loading them on first call.
+    pub fn all(&self, lua: &Lua) -> mlua::Result<Table> {
+        if let Some(x) = self.all.get() {
+            return Ok(x.clone());
+        }
+        let all = lua.create_table()?;
+        for name in &self.names {
+            if let Some(m) = self.get(lua, name)? {
+                all.push(m)?;
+            }
+        }
+        Ok(self.all.get_or_init(|| all).clone())
+    }
+}
+
+/// Short info about all mods from the mod list, loaded on first access.
+struct LazyShortMods {
+    cache: Cache,
+    /// sequence of mods, and the same mods by name
+    list: OnceCell<(Table, Table)>,
+}
+
+impl LazyShortMods {
+    fn list(&self, lua: &Lua) -> mlua::Result<&(Table, Table)> {
+        if let Some(x) = self.list.get() {
+            return Ok(x);
+        }
+        let mods = self
+            .cache
+            .load_short_mod_list()
+            .map_err(mlua::Error::external)?;
+        let (all, by_name) = (lua.create_table()?, lua.create_table()?);
+        for m in mods {
+            let name = m.name.clone();
+            let m = lua.create_userdata(m)?;
+            all.push(&m)?;
+            by_name.raw_set(name, m)?;
+        }
+        Ok(self.list.get_or_init(|| (all, by_name)))
+    }
+}
+
+/// Table acting like sequence returned by `all`, whose entries can also be
+/// looked up by name with `get`.
+///
+/// Sequence is only created once script uses it, by indexing with numbers,
+/// taking length, or iterating with `pairs` or `ipairs`. Names accepted by
+/// `get` are returned by `__names` metamethod, for completion in the REPL.
+fn lazy_table(
+    lua: &Lua,
+    all: impl Fn(&Lua) -> mlua::Result<Table> + 'static,
+    get: impl Fn(&Lua, &str) -> mlua::Result<Value> + 'static,
+    names: impl Fn(&Lua) -> mlua::Result<Vec<String>> + 'static,
+) -> mlua::Result<Table> {
+    let all = Rc::new(all);
+    // script can replace them
+    let next = lua.globals().get::<Function>("next")?;
+    let ipairs = lua.globals().get::<Function>("ipairs")?;
+
+    let meta = lua.create_table()?;
+    let all_index = all.clone();
+    meta.set(
+        "__index",
+        lua.create_function(move |lua, (_, key): (Value, Value)| match key {
+            Value::String(name) => get(lua, &name.to_str()?),
+            key => all_index(lua)?.raw_get::<Value>(key),
+        })?,
+    )?;
+    let all_len = all.clone();
+    meta.set(
+        "__len",
+        lua.create_function(move |lua, _: MultiValue| {
+            Ok(all_len(lua)?.raw_len())
+        })?,
+    )?;
+    let all_pairs = all.clone();
+    meta.set(
+        "__pairs",
+        lua.create_function(move |lua, _: Value| {
+            Ok((next.clone(), all_pairs(lua)?, Value::Nil))
+        })?,
+    )?;
+    meta.set(
+        "__ipairs",
+        lua.create_function(move |lua, _: Value| {
+            ipairs.call::<MultiValue>(all(lua)?)
+        })?,
+    )?;
+    meta.set(
+        "__names",
+        lua.create_function(move |lua, _: Value| names(lua))?,
+    )?;
+    let table = lua.create_table()?;
+    table.set_metatable(Some(meta));
+    Ok(table)
+}
+
+/// Sets globals `mods` and `mods_short`.
+pub(crate) fn set_mod_globals(
+    lua: &Lua,
+    cache: &Cache,
+    mods: Rc<LazyMods>,
+) -> mlua::Result<()> {
+    let (mods_get, mods_names) = (mods.clone(), mods.clone());
+    let table = lazy_table(
+        lua,
+        move |lua| mods.all(lua),
+        move |lua, name| {
+            Ok(mods_get.get(lua, name)?.map_or(Value::Nil, Value::UserData))
+        },
+        move |_| Ok(mods_names.names().iter().cloned().collect()),
+    )?;
+    lua.globals().set("mods", table)?;
+
+    let short = Rc::new(LazyShortMods {
+        cache: cache.clone(),
+        list: OnceCell::new(),
+    });
+    let (short_get, short_names) = (short.clone(), short.clone());
+    let table = lazy_table(
+        lua,
+        move |lua| Ok(short.list(lua)?.0.clone()),
+        move |lua, name| short_get.list(lua)?.1.raw_get(name),
+        move |lua| {
+            let by_name = &short_names.list(lua)?.1;
+            by_name.pairs::<String, Value>().map(|x| Ok(x?.0)).collect()
+        },
+    )?;
+    lua.globals().set("mods_short", table)?;
+    Ok(())
+}
@@ -0,0 +1,453 @@
+mod crater;
+mod defs;
+mod lazy;
+mod output;
+mod repl;
+mod sandbox;
+
+use std::{path::Path, rc::Rc};
+
+use mlua::{
+    ChunkMode, Function, IntoLua, IntoLuaMulti, Lua, MultiValue, UserData,
+    Value, Variadic,
+};
+
+use crate::{
+    Error,
+    cache::Cache,
+    changelog::{Changelog, ChangelogCategory, ChangelogVersion},
+    deserialization::{
+        Dep, DepPrefix, FullInfoJson, Image, LatestRelease, License, Mod,
+        ModFull, Release, ShortInfoJson,
+    },
+    lua::{
+        crater::crater_table,
+        lazy::{LazyMods, set_mod_globals},
+        output::add_output_helpers,
+    },
+};
+
+pub use defs::update_defs;
+pub use repl::run_repl;
+pub use sandbox::{DEFAULT_INSTRUCTION_LIMIT, DEFAULT_MEMORY_LIMIT, Sandbox};
+
+/// How userdata is named in lua definitions, and printed in repl.
+pub(crate) struct Description {
+    pub name: &'static str,
+    /// descriptions of fields, written into lua definitions
+    pub docs: &'static [(&'static str, &'static str)],
+    /// field shown next to type name when userdata isn't printed in full
+    pub label: Option<&'static str>,
+    /// whether userdata is printed through its `__tostring`
+    pub tostring: bool,
+}
+
+impl Description {
+    const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            docs: &[],
+            label: None,
+            tostring: false,
+        }
+    }
+}
+
+pub(crate) trait Describe: UserData + 'static {
+    const DESCRIPTION: Description;
+}
+
+/// Runs lua script with globals from [`set_globals`], and `arg` table of
+/// script's path (at index 0) and arguments.
+///
+/// Errors of the script are returned with lua traceback, and errors of mods
+/// that couldn't be loaded are passed to `on_error`.
+///
+/// With `sandbox`, script runs with its restrictions.
+pub fn run_lua(
+    cache: &Cache,
+    lua_script: &Path,
+    args: &[String],
+    sandbox: Option<&Sandbox>,
+    on_error: impl Fn(&Error) + 'static,
+) -> Result<(), Error> {
+    let lua = Lua::new();
+    set_globals(&lua, cache, on_error)?;
+    let run = || {
+        let arg = lua.create_table()?;
+        arg.raw_set(0, lua_script.display().to_string())?;
+        for (i, x) in args.iter().enumerate() {
+            arg.raw_set(i + 1, x.as_str())?;
+        }
+        lua.globals().set("arg", arg)?;
+
+        let mut chunk = lua.load(lua_script);
+        if let Some(sandbox) = sandbox {
+            sandbox.apply(&lua)?;
+            chunk = chunk.set_mode(ChunkMode::Text);
+        }
+        let chunk = chunk.into_function()?;
+        let args = Variadic::from_iter(args.iter().map(|x| x.as_str()));
+        mlua::Result::Ok(call_traced(&lua, chunk, args)?.map(|_| ()))
+    };
+    // mlua's error isn't `Send` without `send` feature
+    match run() {
+        Ok(result) => result.map_err(Error::Lua),
+        Err(e) => Err(Error::Lua(e.to_string())),
+    }
+}
+
+/// Sets globals `mods`, holding metadata of cached mods, `mods_short`, holding
+/// short info from the mod list, and `crater` table of helper functions.
+///
+/// Metadata is only loaded once scripts use it, and errors of mods that
+/// couldn't be loaded are passed to `on_error`.
+fn set_globals(
+    lua: &Lua,
+    cache: &Cache,
+    on_error: impl Fn(&Error) + 'static,
+) -> Result<Rc<LazyMods>, Error> {
+    let mods = Rc::new(LazyMods::new(lua, cache, on_error)?);
+    let set = || {
+        set_mod_globals(lua, cache, mods.clone())?;
+        let crater = crater_table(lua, mods.clone())?;
+        add_output_helpers(lua, &crater)?;
+        lua.globals().set("crater", crater)
+    };
+    set().map_err(|e| Error::Lua(e.to_string()))?;
+    Ok(mods)
+}
+
+/// Calls function, returning its values, or its error with lua traceback.
+fn call_traced(
+    lua: &Lua,
+    function: Function,
+    args: impl IntoLuaMulti,
+) -> mlua::Result<Result<MultiValue, String>> {
+    let handler = lua.create_function(|lua, e: Value| {
+        let message = match e {
+            Value::Error(e) => error_message(&e),
+            e => e.to_string()?,
+        };
+        Ok(traceback(lua, &message))
+    })?;
+    let xpcall = lua.globals().get::<Function>("xpcall")?;
+    let mut values = xpcall.call::<MultiValue>((function, handler, args))?;
+    let ok = values
+        .pop_front()
+        .is_some_and(|x| x.as_boolean() == Some(true));
+    Ok(match ok {
+        true => Ok(values),
+        false => Err(values.pop_front().unwrap_or_default().to_string()?),
+    })
+}
+
+/// Message of error raised from rust, without traceback that mlua adds to it.
+fn error_message(e: &mlua::Error) -> String {
+    match e {
+        mlua::Error::CallbackError { cause, .. } => error_message(cause),
+        mlua::Error::RuntimeError(message) => message.clone(),
+        e => e.to_string(),
+    }
+}
+
+/// Error message with stack of functions that were running, like
+/// `debug.traceback`, which isn't available to scripts.
+fn traceback(lua: &Lua, message: &str) -> String {
+    let mut traceback = format!("{message}\nstack traceback:");
+    // level 0 is the error handler
+    for level in 1.. {
+        let Some(frame) = lua.inspect_stack(level) else {
+            break;
+        };
+        let source = frame.source();
+        let place = source.short_src.as_deref().unwrap_or("?");
+        let line = frame.curr_line();
+        let location = match line > 0 {
+            true => format!("{place}:{line}"),
+            false => place.to_string(),
+        };
+        let function = match (source.what, frame.names().name) {
+            ("main", _) => "main chunk".to_string(),
+            (_, Some(name)) => format!("function '{name}'"),
+            ("Lua", None) => format!(
+                "function <{place}:{}>",
+                source.line_defined.unwrap_or_default()
+            ),
+            _ => "?".to_string(),
+        };
+        traceback.push_str(&format!("\n\t{location}: in {function}"));
+    }
+    traceback
+}
+
+impl UserData for Mod {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+        fields.add_field_method_get("latest_release", |_, this| {
+            Ok(this.latest_release.clone())
+        });
+    }
+}
+
+impl Describe for Mod {
+    const DESCRIPTION: Description = Description {
+        label: Some("name"),
+        ..Description::new("ModShort")
+    };
+}
+
+impl UserData for ModFull {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("category", |_, this| {
+            Ok(this.category.to_string())
+        });
+        fields.add_field_method_get("changelog", |_, this| {
+            Ok(this.changelog.clone())
+        });
+        fields.add_field_method_get("created_at", |_, this| {
+            Ok(this.created_at.clone())
+        });
+        fields.add_field_method_get("downloads_count", |_, this| {
+            Ok(this.downloads_count)
+        });
+        fields
+            .add_field_method_get("deprecated", |_, this| Ok(this.deprecated));
+        fields.add_field_method_get("description", |_, this| {
+            Ok(this.description.clone())
+        });
+        fields.add_field_method_get("homepage", |_, this| {
+            Ok(this.homepage.clone())
+        });
+        fields
+            .add_field_method_get("images", |_, this| Ok(this.images.clone()));
+        fields.add_field_method_get("license", |_, this| {
+            Ok(this.license.clone())
+        });
+        fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+        fields.add_field_method_get("owner", |_, this| Ok(this.owner.clone()));
+        fields.add_field_method_get("releases", |_, this| {
+            Ok(this.releases.clone())
+        });
+        fields.add_field_method_get("score", |_, this| Ok(this.score));
+        fields.add_field_method_get("source_url", |_, this| {
+            Ok(this.source_url.clone())
+        });
+        fields.add_field_method_get("summary", |_, this| {
+            Ok(this.summary.clone())
+        });
+        fields.add_field_method_get("tags", |_, this| Ok(this.tags.clone()));
+        fields.add_field_method_get("thumbnail", |_, this| {
+            Ok(this.thumbnail.clone())
+        });
+        fields.add_field_method_get("title", |_, this| Ok(this.title.clone()));
+        fields.add_field_method_get("updated_at", |_, this| {
+            Ok(this.updated_at.clone())
+        });
+    }
+
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        // returns changelog, or nil and error message
+        methods.add_method("parse_changelog", |_, this, ()| {
+            let changelog = this.changelog.as_deref().unwrap_or_default();
+            Ok(match Changelog::parse(changelog) {
+                Ok(x) => (Some(x), None),
+                Err(e) => (None, Some(e.to_string())),
+            })
+        });
+    }
+}
+
+impl Describe for ModFull {
+    const DESCRIPTION: Description = Description {
+        label: Some("name"),
+        ..Description::new("Mod")
+    };
+}
+
+impl UserData for Changelog {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("versions", |_, this| {
+            Ok(this.versions.clone())
+        });
+    }
+
+    fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("since", |_, this, version: String| {
+            let version = version.parse().map_err(mlua::Error::external)?;
+            Ok(this.since(version).cloned().collect::<Vec<_>>())
+        });
+        methods
+            .add_meta_method("__tostring", |_, this, ()| Ok(this.to_string()));
+    }
+}
+
+impl Describe for Changelog {
+    const DESCRIPTION: Description = Description::new("Changelog");
+}
+
+impl UserData for ChangelogVersion {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("version", |_, this| {
+            Ok(this.version.to_string())
+        });
+        fields.add_field_method_get("date", |_, this| Ok(this.date.clone()));
+        fields.add_field_method_get("categories", |_, this| {
+            Ok(this.categories.clone())
+        });
+    }
+}
+
+impl Describe for ChangelogVersion {
+    const DESCRIPTION: Description = Description {
+        label: Some("version"),
+        ..Description::new("ChangelogVersion")
+    };
+}
+
+impl UserData for ChangelogCategory {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+        fields.add_field_method_get("entries", |_, this| {
+            Ok(this.entries.clone())
+        });
+    }
+}
+
+impl Describe for ChangelogCategory {
+    const DESCRIPTION: Description = Description {
+        label: Some("name"),
+        ..Description::new("ChangelogCategory")
+    };
+}
+
+impl UserData for Image {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("id", |_, this| Ok(this.id.clone()));
+        fields.add_field_method_get("thumbnail", |_, this| {
+            Ok(this.thumbnail.clone())
+        });
+        fields.add_field_method_get("url", |_, this| Ok(this.url.clone()));
+    }
+}
+
+impl Describe for Image {
+    const DESCRIPTION: Description = Description {
+        label: Some("id"),
+        ..Description::new("Image")
+    };
+}
+
+impl UserData for License {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("description", |_, this| {
+            Ok(this.description.clone())
+        });
+        fields.add_field_method_get("id", |_, this| Ok(this.id.clone()));
+        fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+        fields.add_field_method_get("title", |_, this| Ok(this.title.clone()));
+        fields.add_field_method_get("url", |_, this| Ok(this.url.clone()));
+    }
+}
+
+impl Describe for License {
+    const DESCRIPTION: Description = Description {
+        label: Some("id"),
+        ..Description::new("License")
+    };
+}
+
+impl<INFO: IntoLua + Clone> UserData for Release<INFO> {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("download_url", |_, this| {
+            Ok(this.download_url.clone())
+        });
+        fields.add_field_method_get("file_name", |_, this| {
+            Ok(this.file_name.clone())
+        });
+        fields.add_field_method_get("info_json", |_, this| {
+            Ok(this.info_json.clone())
+        });
+        fields.add_field_method_get("released_at", |_, this| {
+            Ok(this.released_at.clone())
+        });
+        fields.add_field_method_get("sha1", |_, this| Ok(this.sha1.clone()));
+        fields.add_field_method_get("version", |_, this| {
+            Ok(this.version.clone())
+        });
+    }
+}
+
+impl Describe for Release<FullInfoJson> {
+    const DESCRIPTION: Description = Description {
+        label: Some("version"),
+        ..Description::new("Release")
+    };
+}
+
+impl Describe for LatestRelease {
+    const DESCRIPTION: Description = Description {
+        label: Some("version"),
+        ..Description::new("LatestRelease")
+    };
+}
+
+impl UserData for FullInfoJson {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("dependencies", |_, this| {
+            Ok(this.dependencies.clone())
+        });
+        fields.add_field_method_get("factorio_version", |_, this| {
+            Ok(this.factorio_version.clone())
+        });
+    }
+}
+
+impl Describe for FullInfoJson {
+    const DESCRIPTION: Description = Description::new("InfoJson");
+}
+
+impl UserData for ShortInfoJson {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("factorio_version", |_, this| {
+            Ok(this.factorio_version.clone())
+        });
+    }
+}
+
+impl Describe for ShortInfoJson {
+    const DESCRIPTION: Description = Description::new("ShortInfoJson");
+}
+
+impl UserData for Dep {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("original", |_, this| {
+            Ok(this.original.clone())
+        });
+        fields.add_field_method_get("prefix", |_, this| Ok(this.prefix));
+        fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+        fields.add_field_method_get("version", |_, this| {
+            Ok(this.version.map(|x| x.to_string()).unwrap_or_default())
+        });
+    }
+}
+
+impl Describe for Dep {
+    const DESCRIPTION: Description = Description {
+        label: Some("original"),
+        ..Description::new("Dep")
+    };
+}
+
+impl IntoLua for DepPrefix {
+    fn into_lua(self, lua: &Lua) -> mlua::Result<mlua::Value> {
+        mlua::String::wrap(match self {
+            DepPrefix::Incompatible => "incompatible",
+            DepPrefix::Optional => "optional",
+            DepPrefix::HiddenOptional => "hidden-optional",
+            DepPrefix::LoadOrderIndependent => "load-order-independent",
+            DepPrefix::Required => "required",
+        })
+        .into_lua(lua)
+    }
+}
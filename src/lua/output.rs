@@ -0,0 +1,229 @@
+//! Helpers for writing script results as JSON, CSV, or text files.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    fs,
+};
+
+use mlua::{Lua, Table, Value};
+use serde::{
+    Serialize, Serializer,
+    ser::{Error as _, SerializeMap, SerializeSeq},
+};
+
+use crate::lua::sandbox::check_write;
+
+/// Nesting deeper than this is assumed to be a cycle.
+const MAX_DEPTH: usize = 128;
+
+/// Lua value serialized as JSON.
+///
+/// Tables with only keys `1..n` become arrays (empty tables too), others
+/// become objects with keys in sorted order, so output is stable.
+struct Json<'a> {
+    value: &'a Value,
+    depth: usize,
+}
+
+/// Length of table, if it's a sequence.
+fn sequence_len(table: &Table) -> Option<usize> {
+    let len = table.raw_len();
+    let pairs = table.pairs::<Value, Value>().count();
+    (len == pairs).then_some(len)
+}
+
+/// Lua number, written as integer if it has no fractional part.
+fn number_string(x: f64) -> String {
+    if x.fract() == 0.0 && x.abs() < 1e15 {
+        format!("{}", x as i64)
+    } else {
+        x.to_string()
+    }
+}
+
+impl Json<'_> {
+    fn nested<'b>(&self, value: &'b Value) -> Json<'b> {
+        Json {
+            value,
+            depth: self.depth + 1,
+        }
+    }
+}
+
+impl Serialize for Json<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Value::Nil => s.serialize_unit(),
+            Value::Boolean(x) => s.serialize_bool(*x),
+            Value::Integer(x) => s.serialize_i64(*x),
+            Value::Number(x) if x.fract() == 0.0 && x.abs() < 1e15 => {
+                s.serialize_i64(*x as i64)
+            }
+            Value::Number(x) if x.is_finite() => s.serialize_f64(*x),
+            Value::String(x) => {
+                s.serialize_str(&x.to_str().map_err(S::Error::custom)?)
+            }
+            Value::Table(_) if self.depth >= MAX_DEPTH => {
+                Err(S::Error::custom("table nested too deep, or cyclic"))
+            }
+            Value::Table(table) => match sequence_len(table) {
+                Some(len) => {
+                    let mut seq = s.serialize_seq(Some(len))?;
+                    for value in table.clone().sequence_values::<Value>() {
+                        let value = value.map_err(S::Error::custom)?;
+                        seq.serialize_element(&self.nested(&value))?;
+                    }
+                    seq.end()
+                }
+                None => {
+                    let mut entries = BTreeMap::new();
+                    for pair in table.pairs::<Value, Value>() {
+                        let (key, value) = pair.map_err(S::Error::custom)?;
+                        let key = match key {
+                            Value::Number(x) => number_string(x),
+                            key => key.to_string().map_err(S::Error::custom)?,
+                        };
+                        entries.insert(key, value);
+                    }
+                    let mut map = s.serialize_map(Some(entries.len()))?;
+                    for (key, value) in &entries {
+                        map.serialize_entry(key, &self.nested(value))?;
+                    }
+                    map.end()
+                }
+            },
+            // userdata is written through its `__tostring`
+            Value::UserData(_) => s.serialize_str(
+                &self.value.to_string().map_err(S::Error::custom)?,
+            ),
+            x => Err(S::Error::custom(format!(
+                "can't write {} as json",
+                x.type_name()
+            ))),
+        }
+    }
+}
+
+fn to_json(value: &Value, pretty: bool) -> mlua::Result<String> {
+    let json = Json { value, depth: 0 };
+    match pretty {
+        true => simd_json::to_string_pretty(&json),
+        false => simd_json::to_string(&json),
+    }
+    .map_err(mlua::Error::external)
+}
+
+/// Text of a CSV cell.
+fn cell_text(value: &Value) -> mlua::Result<String> {
+    Ok(match value {
+        Value::Nil => String::new(),
+        Value::Number(x) => number_string(*x),
+        x => x.to_string()?,
+    })
+}
+
+fn write_csv_row(out: &mut String, cells: &[String]) {
+    for (i, cell) in cells.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if cell.contains([',', '"', '\n', '\r']) {
+            let _ = write!(out, "\"{}\"", cell.replace('"', "\"\""));
+        } else {
+            out.push_str(cell);
+        }
+    }
+    out.push('\n');
+}
+
+/// Writes `rows` as CSV.
+///
+/// Rows are either sequences of cells, written without header, or tables of
+/// named cells, written in order of `columns` (all their keys, sorted, if
+/// not given) after a header.
+fn to_csv(rows: &Table, columns: Option<Vec<String>>) -> mlua::Result<String> {
+    let rows = rows
+        .clone()
+        .sequence_values::<Table>()
+        .collect::<mlua::Result<Vec<_>>>()?;
+    let named = columns.is_some()
+        || rows.first().is_some_and(|x| sequence_len(x).is_none());
+    let mut out = String::new();
+    if !named {
+        for row in rows {
+            let cells = row
+                .sequence_values::<Value>()
+                .map(|x| cell_text(&x?))
+                .collect::<mlua::Result<Vec<_>>>()?;
+            write_csv_row(&mut out, &cells);
+        }
+        return Ok(out);
+    }
+    let columns = match columns {
+        Some(x) => x,
+        None => {
+            let mut columns = BTreeSet::new();
+            for row in &rows {
+                for pair in row.pairs::<String, Value>() {
+                    columns.insert(pair?.0);
+                }
+            }
+            columns.into_iter().collect()
+        }
+    };
+    write_csv_row(&mut out, &columns);
+    for row in rows {
+        let cells = columns
+            .iter()
+            .map(|x| cell_text(&row.get::<Value>(x.as_str())?))
+            .collect::<mlua::Result<Vec<_>>>()?;
+        write_csv_row(&mut out, &cells);
+    }
+    Ok(out)
+}
+
+fn write_file(
+    lua: &Lua,
+    path: &str,
+    contents: impl AsRef<[u8]>,
+) -> mlua::Result<()> {
+    check_write(lua, path)?;
+    fs::write(path, contents).map_err(|e| {
+        mlua::Error::runtime(format!("couldn't write {path}: {e}"))
+    })
+}
+
+/// Adds `to_json`, `write_json`, `write_csv`, and `write_text` functions into
+/// `crater` table.
+pub fn add_output_helpers(lua: &Lua, crater: &Table) -> mlua::Result<()> {
+    crater.set(
+        "to_json",
+        lua.create_function(|_, (value, pretty): (Value, Option<bool>)| {
+            to_json(&value, pretty.unwrap_or(false))
+        })?,
+    )?;
+    crater.set(
+        "write_json",
+        lua.create_function(|lua, (path, value): (String, Value)| {
+            let mut json = to_json(&value, true)?;
+            json.push('\n');
+            write_file(lua, &path, json)
+        })?,
+    )?;
+    crater.set(
+        "write_csv",
+        lua.create_function(
+            |lua, (path, rows, columns): (String, Table, Option<Vec<String>>)| {
+                write_file(lua, &path, to_csv(&rows, columns)?)
+            },
+        )?,
+    )?;
+    crater.set(
+        "write_text",
+        lua.create_function(|lua, (path, text): (String, mlua::String)| {
+            write_file(lua, &path, text.as_bytes())
+        })?,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,389 @@
+//! Interactive lua session over cached mods.
+
+use core::fmt::Write;
+use std::{collections::BTreeSet, io};
+
+use mlua::{AnyUserData, Function, Lua, ObjectLike, Table, Value};
+use rustyline::{
+    CompletionType, Config, Context, Editor, Helper,
+    completion::Completer,
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::{ValidationContext, ValidationResult, Validator},
+};
+
+use crate::{
+    Error,
+    cache::Cache,
+    lua::{
+        call_traced,
+        defs::{Class, classes},
+        error_message, set_globals,
+    },
+};
+
+/// Tables and userdata nested deeper than this aren't printed in full.
+const MAX_DEPTH: usize = 3;
+/// Entries of a table printed before the rest is cut.
+const MAX_ENTRIES: usize = 20;
+/// Characters of a string printed before the rest is cut.
+const MAX_STRING: usize = 200;
+/// Tables of values fitting into this many characters are printed on one line.
+const INLINE_WIDTH: usize = 70;
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function",
+    "goto", "if", "in", "local", "nil", "not", "or", "repeat", "return",
+    "then", "true", "until", "while",
+];
+
+/// Type of userdata created by this crate.
+fn class_of(ud: &AnyUserData) -> Option<Class> {
+    classes().into_iter().find(|x| (x.is)(ud))
+}
+
+fn is_name(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&s)
+}
+
+/// Key of table entry, as written in table constructor.
+fn key_text(key: &Value) -> mlua::Result<String> {
+    Ok(match key {
+        Value::String(x) if is_name(&x.to_str()?) => x.to_str()?.to_string(),
+        key => {
+            let mut text = String::new();
+            pretty(&mut text, key, MAX_DEPTH)?;
+            format!("[{text}]")
+        }
+    })
+}
+
+/// Entries of table as `pairs` iterates them, respecting `__pairs`, so lazy
+/// tables like `mods` aren't empty.
+fn pairs(table: &Table) -> mlua::Result<Vec<(Value, Value)>> {
+    let pairs = match table.metatable() {
+        Some(meta) => meta.raw_get::<Option<Function>>("__pairs")?,
+        None => None,
+    };
+    let Some(pairs) = pairs else {
+        return table.pairs().collect();
+    };
+    let (next, state, mut key) =
+        pairs.call::<(Function, Value, Value)>(table)?;
+    let mut entries = Vec::new();
+    loop {
+        let (next_key, value) = next.call::<(Value, Value)>((&state, key))?;
+        if next_key.is_nil() {
+            return Ok(entries);
+        }
+        entries.push((next_key.clone(), value));
+        key = next_key;
+    }
+}
+
+/// Writes value like lua constructor, with big values shortened.
+fn pretty(out: &mut String, value: &Value, depth: usize) -> mlua::Result<()> {
+    match value {
+        Value::String(x) => {
+            let x = x.to_string_lossy();
+            let _ = match x.char_indices().nth(MAX_STRING) {
+                Some((end, _)) => write!(out, "{:?}...", &x[..end]),
+                None => write!(out, "{x:?}"),
+            };
+        }
+        Value::Table(table) => {
+            let mut entries = pairs(table)?;
+            if table.len()? as usize == entries.len() {
+                entries.sort_by_key(|(key, _)| key.as_i64());
+                let values = entries.into_iter().map(|(_, x)| (None, x));
+                write_entries(out, "", values.collect(), depth)?;
+            } else {
+                let mut named = entries
+                    .into_iter()
+                    .map(|(key, value)| Ok((key_text(&key)?, value)))
+                    .collect::<mlua::Result<Vec<_>>>()?;
+                named.sort_by(|a, b| a.0.cmp(&b.0));
+                let named = named.into_iter().map(|(k, v)| (Some(k), v));
+                write_entries(out, "", named.collect(), depth)?;
+            }
+        }
+        Value::UserData(ud) => match class_of(ud) {
+            Some(class) if !class.description.tostring => {
+                let mut prefix = class.description.name.to_string();
+                if let Some(label) = class.description.label {
+                    prefix.push(' ');
+                    pretty(&mut prefix, &ud.get(label)?, MAX_DEPTH)?;
+                }
+                prefix.push(' ');
+                let mut fields = Vec::new();
+                for (field, _) in (class.members)().fields {
+                    let value = ud.get::<Value>(field.as_str())?;
+                    if !value.is_nil() {
+                        fields.push((Some(field), value));
+                    }
+                }
+                write_entries(out, &prefix, fields, depth)?;
+            }
+            _ => out.push_str(&value.to_string()?),
+        },
+        value => out.push_str(&value.to_string()?),
+    }
+    Ok(())
+}
+
+/// Writes entries of table or fields of userdata, in braces after `prefix`.
+fn write_entries(
+    out: &mut String,
+    prefix: &str,
+    entries: Vec<(Option<String>, Value)>,
+    depth: usize,
+) -> mlua::Result<()> {
+    out.push_str(prefix);
+    if entries.is_empty() {
+        out.push_str("{}");
+        return Ok(());
+    }
+    if depth >= MAX_DEPTH {
+        out.push_str("{...}");
+        return Ok(());
+    }
+    let mut items = Vec::new();
+    for (key, value) in entries.iter().take(MAX_ENTRIES) {
+        let mut item =
+            key.as_ref().map(|x| format!("{x} = ")).unwrap_or_default();
+        pretty(&mut item, value, depth + 1)?;
+        items.push(item);
+    }
+    let more = entries.len() - items.len();
+    let width = items.iter().map(|x| x.len() + 2).sum::<usize>();
+    if more == 0
+        && width <= INLINE_WIDTH
+        && items.iter().all(|x| !x.contains('\n'))
+    {
+        let _ = write!(out, "{{ {} }}", items.join(", "));
+        return Ok(());
+    }
+    let indent = "  ".repeat(depth + 1);
+    out.push('{');
+    for item in items {
+        let _ = write!(out, "\n{indent}{item},");
+    }
+    if more > 0 {
+        let _ = write!(out, "\n{indent}-- {more} more");
+    }
+    let _ = write!(out, "\n{}}}", "  ".repeat(depth));
+    Ok(())
+}
+
+/// Compiles input as expression, whose values are printed, or as statements.
+fn compile(lua: &Lua, input: &str) -> mlua::Result<Function> {
+    lua.load(format!("return {input}"))
+        .set_name("=repl")
+        .into_function()
+        .or_else(|_| lua.load(input).set_name("=repl").into_function())
+}
+
+struct ReplHelper {
+    lua: Lua,
+}
+
+impl ReplHelper {
+    /// Names of globals, and keywords.
+    fn globals(&self) -> BTreeSet<String> {
+        let globals = self.lua.globals();
+        let names = globals.pairs::<String, Value>().filter_map(|x| x.ok());
+        let names = names.map(|(name, _)| name).filter(|x| is_name(x));
+        names
+            .chain(KEYWORDS.iter().map(|x| x.to_string()))
+            .collect()
+    }
+
+    /// Names of fields of `object`, or its methods if `methods` is set.
+    ///
+    /// `object` is only a chain of names and indexes, so this doesn't call
+    /// anything besides `__index` and `__names` metamethods. Names of mods in
+    /// lazy tables come from the latter, without loading the mods.
+    fn fields(&self, object: &str, methods: bool) -> BTreeSet<String> {
+        let source = format!("return {object}");
+        let Ok(value) = self.lua.load(source).eval::<Value>() else {
+            return BTreeSet::new();
+        };
+        match value {
+            Value::Table(table) => {
+                let names = table.metatable().and_then(|x| {
+                    x.raw_get::<Option<Function>>("__names").ok().flatten()
+                });
+                match names {
+                    Some(_) if methods => BTreeSet::new(),
+                    Some(names) => names
+                        .call::<Vec<String>>(&table)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                    None => table
+                        .pairs::<Value, Value>()
+                        .filter_map(|x| x.ok())
+                        .filter(|(_, x)| !methods || x.is_function())
+                        .filter_map(|(name, _)| match name {
+                            Value::String(x) => {
+                                x.to_str().ok().map(|x| x.to_string())
+                            }
+                            _ => None,
+                        })
+                        .collect(),
+                }
+            }
+            Value::UserData(ud) => {
+                let members = class_of(&ud).map(|x| (x.members)());
+                match members {
+                    Some(x) if methods => x.methods.into_iter().collect(),
+                    Some(x) => x.fields.into_iter().map(|x| x.0).collect(),
+                    None => BTreeSet::new(),
+                }
+            }
+            _ => BTreeSet::new(),
+        }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let start = before.trim_end_matches(is_name_char).len();
+        let word = &before[start..];
+        let separator = before[..start].chars().last();
+        let names = match separator {
+            Some(separator @ ('.' | ':')) => {
+                let object = &before[..start - 1];
+                let object_start = object
+                    .trim_end_matches(|c| {
+                        is_name_char(c) || ".[]\"'".contains(c)
+                    })
+                    .len();
+                self.fields(&object[object_start..], separator == ':')
+            }
+            _ => self.globals(),
+        };
+        let names = names
+            .into_iter()
+            .filter(|x| x.starts_with(word))
+            .collect::<Vec<_>>();
+        if separator != Some('.') || names.iter().all(|x| is_name(x)) {
+            let names = names.into_iter().filter(|x| is_name(x));
+            return Ok((start, names.collect()));
+        }
+        // names like `some-mod` replace the dot, to be completed as index
+        let names = names.into_iter().map(|x| match is_name(&x) {
+            true => format!(".{x}"),
+            false => format!("[{x:?}]"),
+        });
+        Ok((start - 1, names.collect()))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {
+    // asks for more lines while input isn't finished, e.g. in `function`
+    fn validate(
+        &self,
+        ctx: &mut ValidationContext,
+    ) -> rustyline::Result<ValidationResult> {
+        Ok(match compile(&self.lua, ctx.input()) {
+            Err(mlua::Error::SyntaxError {
+                incomplete_input: true,
+                ..
+            }) => ValidationResult::Incomplete,
+            _ => ValidationResult::Valid(None),
+        })
+    }
+}
+
+impl Helper for ReplHelper {}
+
+fn editor_error(e: ReadlineError) -> Error {
+    match e {
+        ReadlineError::Io(e) => Error::Io(e),
+        e => Error::Io(io::Error::other(e.to_string())),
+    }
+}
+
+/// Runs interactive lua session with the same globals as scripts get, except
+/// `arg`.
+///
+/// Values of expressions are printed, with fields of userdata. History is kept
+/// in cache directory.
+pub fn run_repl(cache: &Cache) -> Result<(), Error> {
+    let lua = Lua::new();
+    let mods = set_globals(&lua, cache, |e| eprintln!("error: {e}"))?;
+
+    let config = Config::builder()
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor = Editor::<ReplHelper, DefaultHistory>::with_config(config)
+        .map_err(editor_error)?;
+    editor.set_helper(Some(ReplHelper { lua: lua.clone() }));
+    let history = cache.root().join("repl_history.txt");
+    // there's no history on first run
+    let _ = editor.load_history(&history);
+
+    eprintln!(
+        "{} cached mods are in `mods`, loaded on first use, helpers are in \
+        `crater`",
+        mods.len()
+    );
+    eprintln!("tab completes names, ctrl-d exits");
+    loop {
+        let input = match editor.readline("> ") {
+            Ok(x) => x,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(editor_error(e)),
+        };
+        if input.trim().is_empty() {
+            continue;
+        }
+        editor.add_history_entry(&input).map_err(editor_error)?;
+        let values = compile(&lua, &input)
+            .and_then(|x| call_traced(&lua, x, ()))
+            .and_then(|values| {
+                values
+                    .map_err(mlua::Error::runtime)?
+                    .iter()
+                    .map(|x| {
+                        let mut out = String::new();
+                        pretty(&mut out, x, 0)?;
+                        Ok(out)
+                    })
+                    .collect::<mlua::Result<Vec<_>>>()
+            });
+        match values {
+            // like `print`
+            Ok(values) if !values.is_empty() => {
+                println!("{}", values.join("\t"))
+            }
+            Ok(_) => (),
+            Err(e) => eprintln!("error: {}", error_message(&e)),
+        }
+    }
+    editor.save_history(&history).map_err(editor_error)
+}
@@ -0,0 +1,200 @@
+//! Restrictions for running scripts that aren't trusted.
+
+use core::cell::Cell;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use mlua::{
+    Debug, Function, HookTriggers, Lua, MultiValue, Table, Value, Variadic,
+    VmState,
+};
+
+pub const DEFAULT_MEMORY_LIMIT: usize = 512 * 1024 * 1024;
+pub const DEFAULT_INSTRUCTION_LIMIT: u64 = 10_000_000_000;
+
+/// Instructions between checks of instruction limit.
+const HOOK_INTERVAL: u32 = 1000;
+
+/// Makes `load`, `loadfile`, and `dofile` refuse precompiled chunks, which
+/// can crash lua.
+const TEXT_ONLY_LOADERS: &str = r#"
+local load, loadfile = load, loadfile
+function _G.load(chunk, name, _, ...)
+    return load(chunk, name, "t", ...)
+end
+function _G.loadfile(path, _, ...)
+    return loadfile(path, "t", ...)
+end
+function _G.dofile(path)
+    return assert(loadfile(path, "t"))()
+end
+"#;
+
+/// Limits of a sandboxed script.
+///
+/// Sandboxed scripts can't run programs, and can only write files inside
+/// output directory, both with lua's `io` and `os`, and `crater` helpers.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    pub output_dir: PathBuf,
+    /// bytes that lua can allocate after globals are set, including userdata
+    /// of mods loaded once script uses them (but not their fields kept by
+    /// rust)
+    pub memory_limit: usize,
+    /// lua instructions that script can run
+    pub instruction_limit: u64,
+}
+
+impl Sandbox {
+    pub fn new(output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            output_dir: output_dir.into(),
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            instruction_limit: DEFAULT_INSTRUCTION_LIMIT,
+        }
+    }
+
+    /// Applies restrictions to lua state, once its globals are set.
+    pub(crate) fn apply(&self, lua: &Lua) -> mlua::Result<()> {
+        fs::create_dir_all(&self.output_dir)
+            .and_then(|()| fs::canonicalize(&self.output_dir))
+            .map(|x| lua.set_app_data(OutputDir(x)))
+            .map_err(|e| {
+                mlua::Error::runtime(format!(
+                    "couldn't create output directory {}: {e}",
+                    self.output_dir.display()
+                ))
+            })?;
+
+        let globals = lua.globals();
+        let os = globals.get::<Table>("os")?;
+        os.set("execute", Value::Nil)?;
+        // creates files outside output directory
+        os.set("tmpname", Value::Nil)?;
+        let remove = os.get::<Function>("remove")?;
+        os.set(
+            "remove",
+            lua.create_function(move |lua, path: String| {
+                check_write(lua, &path)?;
+                remove.call::<MultiValue>(path)
+            })?,
+        )?;
+        let rename = os.get::<Function>("rename")?;
+        os.set(
+            "rename",
+            lua.create_function(move |lua, (from, to): (String, String)| {
+                check_write(lua, &from)?;
+                check_write(lua, &to)?;
+                rename.call::<MultiValue>((from, to))
+            })?,
+        )?;
+
+        let io = globals.get::<Table>("io")?;
+        io.set("popen", Value::Nil)?;
+        io.set("tmpfile", Value::Nil)?;
+        let open = io.get::<Function>("open")?;
+        io.set(
+            "open",
+            lua.create_function(
+                move |lua, (path, mode): (String, Option<String>)| {
+                    let mode = mode.unwrap_or_else(|| "r".into());
+                    if mode.contains(['w', 'a', '+']) {
+                        check_write(lua, &path)?;
+                    }
+                    open.call::<MultiValue>((path, mode))
+                },
+            )?,
+        )?;
+        let output = io.get::<Function>("output")?;
+        io.set(
+            "output",
+            lua.create_function(move |lua, file: Variadic<Value>| {
+                if let Some(Value::String(path)) = file.first() {
+                    check_write(lua, &path.to_str()?)?;
+                }
+                output.call::<MultiValue>(file)
+            })?,
+        )?;
+        lua.load(TEXT_ONLY_LOADERS).set_name("=sandbox").exec()?;
+
+        self.limit_instructions(lua)?;
+        let used = lua.used_memory();
+        lua.set_memory_limit(used.saturating_add(self.memory_limit))?;
+        Ok(())
+    }
+
+    /// Sets hook counting instructions of main thread, and of coroutines
+    /// created by script, which would otherwise run without limit.
+    fn limit_instructions(&self, lua: &Lua) -> mlua::Result<()> {
+        let triggers = HookTriggers::new().every_nth_instruction(HOOK_INTERVAL);
+        let (count, limit) = (Rc::new(Cell::new(0u64)), self.instruction_limit);
+        let hook = move |_: &Lua, _: Debug| {
+            count.set(count.get() + HOOK_INTERVAL as u64);
+            match count.get() > limit {
+                true => Err(mlua::Error::runtime(format!(
+                    "instruction limit of {limit} exceeded"
+                ))),
+                false => Ok(VmState::Continue),
+            }
+        };
+        lua.set_hook(triggers, hook.clone());
+
+        let coroutine = lua.globals().get::<Table>("coroutine")?;
+        let create_hook = hook.clone();
+        coroutine.set(
+            "create",
+            lua.create_function(move |lua, f: Function| {
+                let thread = lua.create_thread(f)?;
+                thread.set_hook(triggers, create_hook.clone());
+                Ok(thread)
+            })?,
+        )?;
+        coroutine.set(
+            "wrap",
+            lua.create_function(move |lua, f: Function| {
+                let thread = lua.create_thread(f)?;
+                thread.set_hook(triggers, hook.clone());
+                lua.create_function(move |_, args: MultiValue| {
+                    thread.resume::<MultiValue>(args)
+                })
+            })?,
+        )?;
+        Ok(())
+    }
+}
+
+/// Canonical output directory of sandboxed script.
+struct OutputDir(PathBuf);
+
+/// Fails if lua state is sandboxed, and `path` is outside its output
+/// directory.
+pub(crate) fn check_write(lua: &Lua, path: &str) -> mlua::Result<()> {
+    let Some(output_dir) = lua.app_data_ref::<OutputDir>() else {
+        return Ok(());
+    };
+    let denied = || {
+        mlua::Error::runtime(format!(
+            "sandboxed script can't write {path}, only files in {}",
+            output_dir.0.display()
+        ))
+    };
+    let file = Path::new(path);
+    // file doesn't have to exist yet, but its directory does
+    let resolved = match fs::canonicalize(file) {
+        Ok(x) => x,
+        Err(_) => {
+            let dir = file.parent().filter(|x| !x.as_os_str().is_empty());
+            let dir = fs::canonicalize(dir.unwrap_or(Path::new(".")))
+                .map_err(|_| denied())?;
+            let name = file.file_name().ok_or_else(denied)?;
+            dir.join(name)
+        }
+    };
+    match resolved.starts_with(&output_dir.0) {
+        true => Ok(()),
+        false => Err(denied()),
+    }
+}
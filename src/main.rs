@@ -1,33 +1,40 @@
-mod deserialization;
-mod download;
 mod gui;
-#[cfg(feature = "lua")]
-mod lua;
 
-use core::mem;
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 use std::{
     collections::{BTreeMap, BTreeSet},
-    fs::{self, File},
-    io::Write,
+    path::Path,
     path::PathBuf,
+    process, thread,
 };
 
 use clap::Parser;
-use gui::run_gui;
-
 #[cfg(feature = "lua")]
-use crate::lua::run_lua;
-use crate::{
-    deserialization::{Dep, DepPrefix, LatestRelease, ModFull, ModList},
-    download::{download_mod_list, download_mods, download_mods_meta_full},
+use factorio_crater::lua::{
+    DEFAULT_INSTRUCTION_LIMIT, DEFAULT_MEMORY_LIMIT, Sandbox, run_lua,
+    run_repl, update_defs,
 };
-
-const INTERNAL_MODS: &[&str] =
-    &["base", "elevated-rails", "quality", "space-age"];
-const USER_AGENT: &str = "factorio-crater/0.1.0 (by Shadow0133 aka Aurora)";
-const APP_ID: &str = "factorio-crater";
-
-type Error = Box<dyn core::error::Error + Send + Sync + 'static>;
+use factorio_crater::{
+    Error,
+    analysis::{
+        BrokenReport, conflicts::mod_conflicts, find_broken_mods, history,
+        latest_mods_info,
+    },
+    cache::{
+        Cache, doctor,
+        images::{DEFAULT_MAX_SIZE, ImageCache, asset_url},
+    },
+    changelog::Changelog,
+    download::{Progress, download_images, download_mods},
+    headless::{DEFAULT_TIMEOUT, Headless, Outcome, crater_run},
+    instance::Instance,
+    search::{Filters, SearchIndex, SortBy},
+    version::Version,
+};
+use gui::run_gui;
 
 #[derive(clap::Parser)]
 struct Opt {
@@ -41,8 +48,42 @@ struct Opt {
 #[derive(clap::Subcommand, Clone)]
 enum Command {
     #[cfg(feature = "lua")]
+    /// Run lua script, passing arguments after `--` to it
     Run {
         lua_script: PathBuf,
+        /// Don't let script run programs, or write files outside output
+        /// directory, and limit its memory and instructions
+        #[arg(long)]
+        sandbox: bool,
+        /// Directory that sandboxed script can write into
+        #[arg(long, requires = "sandbox", default_value = ".")]
+        output_dir: PathBuf,
+        /// Memory that sandboxed script can allocate, in MiB
+        #[arg(
+            long,
+            requires = "sandbox",
+            default_value_t = DEFAULT_MEMORY_LIMIT / 1024 / 1024
+        )]
+        memory_limit: usize,
+        /// Lua instructions that sandboxed script can run
+        #[arg(long, requires = "sandbox", default_value_t = DEFAULT_INSTRUCTION_LIMIT)]
+        instruction_limit: u64,
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    #[cfg(feature = "lua")]
+    /// Interactive lua session, with the same globals as scripts
+    Repl,
+    #[cfg(feature = "lua")]
+    /// Update definitions of userdata in lua definitions file, generating
+    /// them from rust types
+    LuaDefs {
+        #[arg(default_value = "defs.lua")]
+        path: PathBuf,
+        /// Only check that definitions are up to date, and methods are
+        /// documented
+        #[arg(long)]
+        check: bool,
     },
     Download {
         #[arg(short = 'f')]
@@ -50,281 +91,540 @@ enum Command {
         mods: Vec<String>,
     },
     Gui,
-    FindBrokenMods,
+    FindBrokenMods {
+        /// Analyse for a game without space age expansion
+        #[arg(long)]
+        no_space_age: bool,
+    },
+    /// List incompatibilities between given mods and their dependencies
+    Conflicts {
+        mods: Vec<String>,
+    },
+    /// Load each mod, with its dependencies, in headless factorio, and
+    /// report which ones fail
+    CraterRun {
+        /// Headless factorio executable, e.g. `factorio/bin/x64/factorio`
+        #[arg(long)]
+        factorio_bin: PathBuf,
+        /// Names of mods, or `all-for-version` for every cached mod with a
+        /// release for the version of factorio
+        #[arg(long, required = true, num_args = 1.., value_delimiter = ',')]
+        mods: Vec<String>,
+        /// Directory for write-data directories and logs of each mod
+        #[arg(long, default_value = "crater-run")]
+        work_dir: PathBuf,
+        /// Directory with `player-data.json`, whose credentials are used to
+        /// download mods; factorio's installation directory by default
+        #[arg(long)]
+        player_data_dir: Option<PathBuf>,
+        /// Seconds before factorio is killed
+        #[arg(long, default_value_t = DEFAULT_TIMEOUT.as_secs())]
+        timeout: u64,
+        /// Also run benchmark of created map for this many ticks
+        #[arg(long)]
+        benchmark_ticks: Option<u32>,
+        /// Load mods without space age expansion
+        #[arg(long)]
+        no_space_age: bool,
+    },
+    /// Manage metadata cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Per factorio version compatibility, taking all releases into account
+    VersionMatrix {
+        /// Analyse for a game without space age expansion
+        #[arg(long)]
+        no_space_age: bool,
+    },
+    /// Print changelog of a mod
+    Changelog {
+        name: String,
+        /// Only show versions newer than this one, instead of the one
+        /// installed in factorio instance
+        #[arg(long)]
+        since: Option<Version>,
+        /// Factorio instance whose installed version of the mod is compared
+        /// with the latest one
+        #[arg(short = 'f')]
+        factorio_instance: Option<PathBuf>,
+    },
+    /// Download thumbnails of cached mods, so the GUI can show them offline
+    CacheImages {
+        /// Also download screenshots
+        #[arg(long)]
+        screenshots: bool,
+        /// Size limit of image cache, in MiB
+        #[arg(long, default_value_t = DEFAULT_MAX_SIZE / 1024 / 1024)]
+        max_size: u64,
+    },
+    /// Search cached mods by title, summary, description, owner, and tags
+    Search {
+        query: Vec<String>,
+        #[arg(long)]
+        category: Option<String>,
+        /// Factorio version of latest release
+        #[arg(long)]
+        factorio_version: Option<String>,
+        /// License id, e.g. `default_mit`
+        #[arg(long)]
+        license: Option<String>,
+        #[arg(long)]
+        tag: Option<String>,
+        #[arg(long)]
+        owner: Option<String>,
+        #[arg(long)]
+        deprecated: Option<bool>,
+        #[arg(long)]
+        min_downloads: Option<u64>,
+        #[arg(long)]
+        max_downloads: Option<u64>,
+        /// relevance, downloads, score, created, updated, or title
+        #[arg(long, default_value_t)]
+        sort: SortBy,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Print number of matching mods per category, version, and license
+        #[arg(long)]
+        facets: bool,
+    },
 }
 
-fn main() {
-    let storage = eframe::storage_dir(APP_ID).unwrap();
-    fs::create_dir_all(storage.join("mods")).unwrap();
-    let mod_list_file = storage.join("mods.json");
-
-    let opts = Opt::parse();
-
-    let mut mod_list = File::open(&mod_list_file)
-        .map(|file| simd_json::from_reader::<_, ModList>(file).unwrap())
-        .map(|x| x.results)
-        .unwrap_or_default();
-
-    let mod_json_list: BTreeSet<String> = fs::read_dir(storage.join("mods"))
-        .unwrap()
-        .map(|x| {
-            x.unwrap()
-                .file_name()
-                .into_string()
-                .unwrap()
-                .strip_suffix(".json")
-                .unwrap()
-                .to_string()
-        })
-        .collect();
-    let old_mod_list: BTreeMap<String, Option<String>> = mod_list
-        .iter()
-        .map(|x| {
-            (
-                x.name.clone(),
-                x.latest_release.as_ref().map(|x| x.sha1.clone()),
-            )
-        })
-        .collect();
-    if let Ok(new_mod_list) = download_mod_list() {
-        fs::write(mod_list_file, &new_mod_list).unwrap();
-        let new_mod_list =
-            simd_json::from_slice::<ModList>(&mut new_mod_list.into_bytes())
-                .unwrap()
-                .results;
-        eprintln!("finished downloading the modlist");
-        if !opts.update_all_metadata {
-            let updated_mod_list = new_mod_list
-                .iter()
-                .map(|x| {
-                    (
-                        x.name.as_str(),
-                        x.latest_release.as_ref().map(|x| x.sha1.as_str()),
-                    )
-                })
-                .filter(|&(name, sha1)| {
-                    old_mod_list
-                        .get(name)
-                        .and_then(|x| x.as_deref())
-                        .zip(sha1)
-                        .map(|(a, b)| a != b)
-                        .unwrap_or(true)
-                        | !mod_json_list.contains(name)
-                })
-                .map(|(name, _)| name);
-            // for x in updated_mod_list.clone() {
-            //     eprintln!("{x}");
-            // }
-            download_mods_meta_full(updated_mod_list);
-        } else {
-            download_mods_meta_full(
-                new_mod_list.iter().map(|x| x.name.as_str()),
-            );
-        }
-        mod_list = new_mod_list;
-    }
-
-    match opts.command {
-        None if opts.update_all_metadata => (),
-        None | Some(Command::Gui) => run_gui(),
-        #[cfg(feature = "lua")]
-        Some(Command::Run { lua_script }) => run_lua(&lua_script),
-        Some(Command::Download {
-            factorio_instance,
-            mods,
-        }) => {
-            let mod_version_list: BTreeMap<_, Option<_>> = mod_list
-                .iter()
-                .map(|x| (x.name.as_str(), x.latest_release.as_ref()))
-                .collect();
-            download_mods(
-                &factorio_instance,
-                mods.iter().map(|x| x.as_str()),
-                &mod_version_list,
-            )
-            .unwrap()
-        }
-        Some(Command::FindBrokenMods) => {
-            let mod_version_list: BTreeMap<_, Option<_>> = mod_list
-                .iter()
-                .map(|x| (x.name.as_str(), x.latest_release.as_ref()))
-                .collect();
-            find_broken_mods(mod_version_list);
-        }
-    }
+#[derive(clap::Subcommand, Clone)]
+enum CacheCommand {
+    /// Report cached files that don't match expected format, and why
+    Doctor,
+    /// Report dependency strings that old and new dependency parsers
+    /// disagree on
+    CheckDeps,
 }
 
-fn load_mod_list() -> Vec<ModFull> {
-    let mut mod_list = Vec::new();
-    let mods = eframe::storage_dir(APP_ID)
-        .unwrap()
-        .join("mods")
-        .read_dir()
-        .unwrap();
-    for m in mods {
-        let m = m.unwrap();
-        let Ok(file) = File::open(m.path()) else {
-            continue;
-        };
-        let mod_full: ModFull = simd_json::from_reader(file).unwrap();
-        mod_list.push(mod_full);
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {e}");
+        process::exit(1);
     }
-    mod_list
 }
 
-#[derive(Clone)]
-struct ModWithInfo {
-    deprecated: bool,
-    factorio_version: String,
-    dependencies: Vec<Dep>,
+fn report_errors(errors: &[Error]) {
+    for e in errors {
+        eprintln!("error: {e}");
+    }
 }
 
-fn find_broken_mods<'a>(
-    mod_version_list: BTreeMap<&'a str, Option<&'a LatestRelease>>,
-) {
-    eprintln!("all mods: {}", mod_version_list.len());
-
-    let mut mod_map = BTreeMap::new();
-    for (name, latest_version) in mod_version_list {
-        let file = File::open(
-            eframe::storage_dir(APP_ID)
-                .unwrap()
-                .join("mods")
-                .join(format!("{name}.json")),
-        )
-        .unwrap_or_else(|_| panic!("{name}"));
-        let mod_full: ModFull = simd_json::from_reader(file).unwrap();
-        if latest_version.is_some() == mod_full.releases.is_empty() {
-            eprintln!("release mismatch for {name}");
-        }
-        if let Some(release) = mod_full.releases.into_iter().find(|x| {
-            Some(x.version.as_str())
-                == latest_version.as_ref().map(|x| x.version.as_str())
-        }) {
-            mod_map.insert(
-                name,
-                ModWithInfo {
-                    deprecated: mod_full.deprecated,
-                    factorio_version: release.info_json.factorio_version,
-                    dependencies: release.info_json.dependencies,
-                },
-            );
+/// Runs `f`, printing progress it reports to stderr every second.
+fn with_progress<T>(
+    action: &str,
+    label: &str,
+    f: impl FnOnce(&Progress) -> T,
+) -> T {
+    let progress = Progress::default();
+    let finished = AtomicBool::new(false);
+    let print = |end: &str| {
+        let done = progress.done.load(Ordering::Relaxed);
+        let total = progress.total.load(Ordering::Relaxed);
+        if total > 0 {
+            eprint!("{action:>12} {done}/{total} {label}{end}");
         }
-    }
-    let mut deprecated = BTreeSet::<String>::new();
-    let mut rest = BTreeMap::<String, ModWithInfo>::new();
-    for (&name, m) in &mod_map {
-        if m.deprecated {
-            deprecated.insert(name.into());
-        } else {
-            rest.insert(name.into(), m.clone());
-        }
-    }
-
-    eprintln!("deprecated: {}", deprecated.len());
-    let mut deprecated_file = File::create("deprecated.txt").unwrap();
-    for name in &deprecated {
-        writeln!(deprecated_file, "{name}",).unwrap();
-    }
-
-    for &m in INTERNAL_MODS {
-        deprecated.remove(m);
-    }
-    let deprecated = deprecated;
-
-    let mut broken = BTreeMap::new();
-    let mut working = BTreeSet::<String>::new();
-    let mut typod = BTreeMap::new();
-    working.extend(INTERNAL_MODS.iter().map(|x| x.to_string()));
-    while !rest.is_empty() {
-        for (name, m) in mem::take(&mut rest) {
-            let iter = m
-                .dependencies
-                .iter()
-                .filter(|x| matches!(x.prefix, DepPrefix::Required));
-            if iter.clone().all(|x| working.contains(&x.name)) {
-                working.insert(name);
-            } else if let Some(typod_dep) = iter.clone().find(|x| {
-                !mod_map.contains_key(&*x.name)
-                    && !INTERNAL_MODS.contains(&x.name.as_str())
-            }) {
-                typod.insert(name, typod_dep.name.clone());
-            } else if iter.clone().any(|x| {
-                deprecated.contains(&x.name) | broken.contains_key(&x.name)
-            }) {
-                let broken_deps = iter
-                    .filter(|x| {
-                        deprecated.contains(&x.name)
-                            | broken.contains_key(&x.name)
-                    })
-                    .map(|x| x.name.clone())
-                    .collect::<Vec<_>>();
-                broken.insert(name, (m, broken_deps));
-            } else {
-                rest.insert(name, m);
+    };
+    thread::scope(|s| {
+        let printer = s.spawn(|| {
+            while !finished.load(Ordering::Relaxed) {
+                print("\r");
+                thread::park_timeout(Duration::from_secs(1));
             }
-        }
-    }
-    eprintln!("rest: {}", rest.len());
-    for (name, m) in rest {
+        });
+        let result = f(&progress);
+        finished.store(true, Ordering::Relaxed);
+        printer.thread().unpark();
+        let _ = printer.join();
+        print("\n");
+        result
+    })
+}
+
+/// Prints dependencies of mods that couldn't be resolved.
+fn print_unresolved(report: &BrokenReport) {
+    eprintln!("rest: {}", report.unresolved.len());
+    for name in &report.unresolved {
         eprintln!("{name}");
-        for dep in m.dependencies {
+        for dep in &report.mods[name].dependencies {
             eprint!("- {}: ", dep.name);
-            if working.contains(&dep.name) {
+            if report.working.contains(&dep.name) {
                 eprint!("working");
             }
-            if broken.contains_key(&dep.name) {
+            if report.broken.contains_key(&dep.name) {
                 eprint!("broken");
             }
-            if deprecated.contains(&dep.name) {
+            if report.deprecated.contains(&dep.name) {
                 eprint!("deprecated");
             }
-            if typod.contains_key(&dep.name) {
+            if report.typod.contains_key(&dep.name) {
                 eprint!("typod");
             }
             eprintln!();
         }
     }
+}
 
-    broken.retain(|_, (m, _)| &*m.factorio_version < "2.0");
-    eprintln!("broken: {}", broken.len());
-    let mut b_file = File::create("broken.txt").unwrap();
-    for (name, (m, _)) in &broken {
-        writeln!(b_file, "{name} for {}", m.factorio_version).unwrap();
-    }
-
-    let mut bwr_file = File::create("broken_with_reason.txt").unwrap();
-    for (name, (m, broken_deps)) in &broken {
-        writeln!(bwr_file, "{name} for {} because of:", m.factorio_version)
-            .unwrap();
-        for broken_dep in broken_deps {
-            writeln!(bwr_file, "  {broken_dep}").unwrap();
+fn run() -> Result<(), Error> {
+    let opts = Opt::parse();
+    // gui refreshes metadata by itself, without waiting for it
+    match opts.command {
+        None if !opts.update_all_metadata => return run_gui(false),
+        Some(Command::Gui) => return run_gui(opts.update_all_metadata),
+        // doesn't need metadata
+        #[cfg(feature = "lua")]
+        Some(Command::LuaDefs { ref path, check }) => {
+            let problems = update_defs(path, check)?;
+            for problem in &problems {
+                eprintln!("{problem}");
+            }
+            if !problems.is_empty() {
+                process::exit(1);
+            }
+            return Ok(());
         }
+        _ => (),
     }
+    let cache = Cache::open_default()?;
 
-    let mut b_file = File::create("broken_for_1.1.txt").unwrap();
-    for (name, (m, _)) in &broken {
-        if m.factorio_version != "1.1" {
-            continue;
+    let update = with_progress("Downloading", "mods metadata", |progress| {
+        cache.update_with_progress(opts.update_all_metadata, progress)
+    });
+    let mod_list = match update {
+        Ok((mod_list, errors)) => {
+            report_errors(&errors);
+            mod_list
         }
-        writeln!(b_file, "{name} for {}", m.factorio_version).unwrap();
-    }
+        Err(e) => {
+            eprintln!("couldn't update metadata: {e}");
+            cache.load_short_mod_list()?
+        }
+    };
 
-    let mut bwrf1_1_file =
-        File::create("broken_with_reason_for_1.1.txt").unwrap();
-    for (name, (m, broken_deps)) in &broken {
-        if m.factorio_version != "1.1" {
-            continue;
+    match opts.command {
+        // only updating metadata, gui was handled above
+        None | Some(Command::Gui) => (),
+        #[cfg(feature = "lua")]
+        Some(Command::LuaDefs { .. }) => (),
+        #[cfg(feature = "lua")]
+        Some(Command::Run {
+            lua_script,
+            sandbox,
+            output_dir,
+            memory_limit,
+            instruction_limit,
+            args,
+        }) => {
+            let sandbox = sandbox.then(|| Sandbox {
+                memory_limit: memory_limit * 1024 * 1024,
+                instruction_limit,
+                ..Sandbox::new(output_dir)
+            });
+            run_lua(&cache, &lua_script, &args, sandbox.as_ref(), |e| {
+                eprintln!("error: {e}")
+            })?
+        }
+        #[cfg(feature = "lua")]
+        Some(Command::Repl) => run_repl(&cache)?,
+        Some(Command::Download {
+            factorio_instance,
+            mods,
+        }) => {
+            let mod_version_list: BTreeMap<_, Option<_>> = mod_list
+                .iter()
+                .map(|x| (x.name.as_str(), x.latest_release.as_ref()))
+                .collect();
+            let errors = with_progress("Downloading", "mods", |progress| {
+                download_mods(
+                    &factorio_instance,
+                    mods.iter().map(|x| x.as_str()),
+                    &mod_version_list,
+                    progress,
+                )
+            })?;
+            report_errors(&errors);
+            if !errors.is_empty() {
+                process::exit(1);
+            }
+        }
+        Some(Command::FindBrokenMods { no_space_age }) => {
+            eprintln!("all mods: {}", mod_list.len());
+            let (mod_map, errors) = latest_mods_info(&cache, &mod_list);
+            report_errors(&errors);
+            let report = find_broken_mods(mod_map, !no_space_age);
+            eprintln!("deprecated: {}", report.deprecated.len());
+            print_unresolved(&report);
+            eprintln!("uninstallable: {}", report.uninstallable.len());
+            eprintln!("broken: {}", report.reported_broken().count());
+            report.write_reports(Path::new("."))?;
+            eprintln!("done");
         }
-        writeln!(
-            bwrf1_1_file,
-            "{name} for {} because of:",
-            m.factorio_version
-        )
-        .unwrap();
-        for broken_dep in broken_deps {
-            writeln!(bwrf1_1_file, "  {broken_dep}").unwrap();
+        Some(Command::Conflicts { mods }) => {
+            let result = mod_conflicts(&cache, &mods)?;
+            for name in &result.unknown {
+                eprintln!("unknown mod: {name}");
+            }
+            for name in &result.required {
+                eprintln!("required: {name}");
+            }
+            for conflict in &result.conflicts {
+                println!("{conflict}");
+            }
+            eprintln!("conflicts: {}", result.conflicts.len());
+            if !result.conflicts.is_empty() {
+                process::exit(1);
+            }
+        }
+        Some(Command::CraterRun {
+            factorio_bin,
+            mods,
+            work_dir,
+            player_data_dir,
+            timeout,
+            benchmark_ticks,
+            no_space_age,
+        }) => {
+            // bin/x64/factorio inside installation directory
+            let player_data_dir = player_data_dir.unwrap_or_else(|| {
+                factorio_bin
+                    .ancestors()
+                    .nth(3)
+                    .unwrap_or(Path::new("."))
+                    .into()
+            });
+            let headless = Headless {
+                timeout: Duration::from_secs(timeout),
+                benchmark_ticks,
+                space_age: !no_space_age,
+                ..Headless::new(factorio_bin)
+            };
+            let all = mods.iter().any(|x| x == "all-for-version");
+            let (outcomes, errors) =
+                with_progress("Processing", "mods", |progress| {
+                    crater_run(
+                        &headless,
+                        &cache,
+                        (!all).then_some(mods.as_slice()),
+                        &work_dir,
+                        &player_data_dir,
+                        progress,
+                        |name, outcome| println!("{name}: {outcome}"),
+                    )
+                })?;
+            report_errors(&errors);
+            let mut counts = BTreeMap::<&str, usize>::new();
+            for outcome in outcomes.values() {
+                *counts.entry(outcome.label()).or_default() += 1;
+            }
+            for (label, count) in &counts {
+                eprintln!("{label}: {count}");
+            }
+            if outcomes.values().any(|x| *x != Outcome::Loaded) {
+                process::exit(1);
+            }
+        }
+        Some(Command::Cache {
+            command: CacheCommand::Doctor,
+        }) => {
+            let diagnoses = doctor::diagnose(&cache)?;
+            let mut broken = 0;
+            for d in &diagnoses {
+                println!("{}:", d.name);
+                if let Some(e) = &d.error {
+                    broken += 1;
+                    println!("  unusable: {e}");
+                }
+                if let Some(e) = &d.strict_error {
+                    println!("  fails strict parsing: {e}");
+                }
+                if let Some(category) = &d.unknown_category {
+                    println!("  unknown category: {category}");
+                }
+                for field in &d.unknown_fields {
+                    println!("  unknown field: {field}");
+                }
+            }
+            eprintln!("files with problems: {}", diagnoses.len());
+            eprintln!("unusable: {broken}");
+            if broken > 0 {
+                process::exit(1);
+            }
+        }
+        Some(Command::Cache {
+            command: CacheCommand::CheckDeps,
+        }) => {
+            let (mod_list, errors) = cache.load_mod_list()?;
+            report_errors(&errors);
+            let (total, disagreements) = doctor::compare_dep_parsers(&mod_list);
+            for d in &disagreements {
+                let mods = d.mods.iter().cloned().collect::<Vec<_>>();
+                println!("{:?} (in {}):", d.original, mods.join(", "));
+                match &d.parsed {
+                    Ok(dep) => println!("  new: {dep}"),
+                    Err(e) => println!("  new: {e}"),
+                }
+                let (prefix, name, version) = &d.legacy;
+                println!(
+                    "  legacy: prefix {:?}, name {name:?}, version {version:?}",
+                    prefix.as_str()
+                );
+            }
+            eprintln!("dependency strings: {total}");
+            eprintln!("disagreements: {}", disagreements.len());
+        }
+        Some(Command::VersionMatrix { no_space_age }) => {
+            let (mod_list, errors) = cache.load_mod_list()?;
+            report_errors(&errors);
+            let (matrix, errors) =
+                history::compat_matrix(&mod_list, !no_space_age);
+            report_errors(&errors);
+            eprintln!(
+                "{:>8} {:>8} {:>8} {:>11} {:>22} {:>8} {:>13}",
+                "version",
+                "mods",
+                "working",
+                "deprecated",
+                "broken by deprecation",
+                "broken",
+                "incompatible",
+            );
+            for v in &matrix {
+                eprintln!(
+                    "{:>8} {:>8} {:>8} {:>11} {:>22} {:>8} {:>13}",
+                    history::short_version(v.factorio_version),
+                    v.mods.len(),
+                    v.working.len(),
+                    v.deprecated.len(),
+                    v.broken_by_deprecation.len(),
+                    v.broken.len(),
+                    v.incompatible.len(),
+                );
+            }
+            history::write_compat_matrix(&matrix, &mod_list, Path::new("."))?;
+            eprintln!("done");
+        }
+        Some(Command::CacheImages {
+            screenshots,
+            max_size,
+        }) => {
+            let (mods, errors) = cache.load_mod_list()?;
+            report_errors(&errors);
+            let images =
+                ImageCache::open(cache.images_dir(), max_size * 1024 * 1024)?;
+            let mut urls = BTreeSet::new();
+            for m in &mods {
+                urls.extend(m.thumbnail.as_deref().map(asset_url));
+                if screenshots {
+                    for image in &m.images {
+                        urls.insert(asset_url(&image.thumbnail));
+                        urls.insert(asset_url(&image.url));
+                    }
+                }
+            }
+            urls.retain(|x| !x.is_empty() && !images.contains(x));
+            let errors = with_progress("Downloading", "images", |progress| {
+                download_images(
+                    &images,
+                    urls.iter().map(|x| x.as_str()),
+                    progress,
+                )
+            })?;
+            report_errors(&errors);
+            eprintln!("cached images: {} MiB", images.size() / 1024 / 1024);
+        }
+        Some(Command::Changelog {
+            name,
+            since,
+            factorio_instance,
+        }) => {
+            let m = cache.load_mod(&name)?;
+            let Some(changelog) = m.changelog.as_deref() else {
+                eprintln!("{name} has no changelog");
+                process::exit(1);
+            };
+            let changelog =
+                Changelog::parse(changelog).map_err(|e| Error::Parse {
+                    mod_name: name.clone(),
+                    field: "changelog".into(),
+                    message: e.to_string(),
+                })?;
+            let installed = match (since, factorio_instance) {
+                (None, Some(path)) => Instance::open(path)?
+                    .installed_mods()?
+                    .into_iter()
+                    .find(|x| x.name == name)
+                    .and_then(|x| x.version),
+                _ => None,
+            };
+            if let Some(version) = installed {
+                eprintln!("installed: {version}");
+            }
+            // whole changelog for mods that aren't installed
+            let since = since.or(installed).unwrap_or(Version::new(0, 0, 0));
+            let mut versions = changelog.since(since).peekable();
+            if versions.peek().is_none() {
+                eprintln!("no changes since {since}");
+            }
+            for version in versions {
+                print!("{version}");
+            }
+        }
+        Some(Command::Search {
+            query,
+            category,
+            factorio_version,
+            license,
+            tag,
+            owner,
+            deprecated,
+            min_downloads,
+            max_downloads,
+            sort,
+            limit,
+            facets,
+        }) => {
+            let (index, errors) = SearchIndex::open(&cache)?;
+            report_errors(&errors);
+            let filters = Filters {
+                category,
+                factorio_version,
+                license,
+                tag,
+                owner,
+                deprecated,
+                min_downloads,
+                max_downloads,
+            };
+            let mut hits = index.search(&query.join(" "), &filters);
+            sort.sort(&mut hits);
+            for hit in hits.iter().take(limit) {
+                let m = hit.m;
+                println!(
+                    "{} ({}) by {}, {} downloads",
+                    m.name, m.title, m.owner, m.downloads_count
+                );
+                if !m.summary.is_empty() {
+                    println!("  {}", m.summary);
+                }
+            }
+            eprintln!("found: {}", hits.len());
+            if facets {
+                let facets = SearchIndex::facets(&hits);
+                let print = |label: &str, counts: &BTreeMap<String, usize>| {
+                    println!("{label}:");
+                    for (value, count) in counts {
+                        println!("  {value}: {count}");
+                    }
+                };
+                print("categories", &facets.categories);
+                print("factorio versions", &facets.factorio_versions);
+                print("licenses", &facets.licenses);
+                println!("deprecated: {}", facets.deprecated);
+            }
         }
     }
-    eprintln!("done");
+    Ok(())
 }
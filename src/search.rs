@@ -0,0 +1,660 @@
+//! Full-text search over cached mods, with filters on their metadata.
+//!
+//! Index is persisted in the cache as `search_index.json`, and only mods whose
+//! cached files changed since it was saved are indexed again.
+
+use core::{cmp::Reverse, fmt, str::FromStr};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    ops::Bound,
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, cache::Cache, deserialization::ModFull};
+
+/// Bumped whenever format of [`IndexedMod`] or tokenization changes, so old
+/// indexes get rebuilt.
+const INDEX_FORMAT: u32 = 1;
+const INDEX_FILE: &str = "search_index.json";
+
+const NAME_WEIGHT: f32 = 3.0;
+const TITLE_WEIGHT: f32 = 3.0;
+const TAG_WEIGHT: f32 = 2.0;
+const OWNER_WEIGHT: f32 = 2.0;
+const SUMMARY_WEIGHT: f32 = 1.5;
+const DESCRIPTION_WEIGHT: f32 = 1.0;
+/// Multiplier for index terms that only start with query term.
+const PREFIX_WEIGHT: f32 = 0.5;
+
+/// Metadata of a mod needed for searching and showing results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedMod {
+    pub name: String,
+    pub title: String,
+    pub owner: String,
+    pub summary: String,
+    pub category: String,
+    pub tags: Vec<String>,
+    /// factorio version of latest release
+    pub factorio_version: Option<String>,
+    pub license: Option<String>,
+    pub deprecated: bool,
+    pub downloads_count: u64,
+    pub score: f32,
+    pub created_at: String,
+    pub updated_at: String,
+    /// weighted count of every term in searched fields
+    terms: BTreeMap<String, f32>,
+    /// modification time of cached file in milliseconds, used to detect
+    /// changes
+    modified: u64,
+}
+
+fn tokenize(s: &str) -> impl Iterator<Item = String> {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_lowercase())
+}
+
+impl IndexedMod {
+    fn new(m: &ModFull, modified: u64) -> Self {
+        let mut terms = BTreeMap::<String, f32>::new();
+        let mut add = |text: &str, weight: f32| {
+            for token in tokenize(text) {
+                *terms.entry(token).or_default() += weight;
+            }
+        };
+        add(&m.name, NAME_WEIGHT);
+        add(&m.title, TITLE_WEIGHT);
+        add(&m.owner, OWNER_WEIGHT);
+        add(&m.summary, SUMMARY_WEIGHT);
+        add(
+            m.description.as_deref().unwrap_or_default(),
+            DESCRIPTION_WEIGHT,
+        );
+        let tags = m.tags.clone().unwrap_or_default();
+        for tag in &tags {
+            add(tag, TAG_WEIGHT);
+        }
+
+        Self {
+            name: m.name.clone(),
+            title: m.title.clone(),
+            owner: m.owner.clone(),
+            summary: m.summary.clone(),
+            category: m.category.to_string(),
+            tags,
+            factorio_version: m
+                .latest_release()
+                .map(|x| x.info_json.factorio_version.clone()),
+            license: m.license.as_ref().map(|x| x.id.clone()),
+            deprecated: m.deprecated,
+            downloads_count: m.downloads_count,
+            score: m.score,
+            created_at: m.created_at.clone(),
+            updated_at: m.updated_at.clone(),
+            terms,
+            modified,
+        }
+    }
+}
+
+/// Restricts search to mods with matching metadata. Unset filters match
+/// everything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Filters {
+    pub category: Option<String>,
+    pub factorio_version: Option<String>,
+    pub license: Option<String>,
+    pub tag: Option<String>,
+    /// matched ignoring case
+    pub owner: Option<String>,
+    pub deprecated: Option<bool>,
+    pub min_downloads: Option<u64>,
+    pub max_downloads: Option<u64>,
+}
+
+impl Filters {
+    pub fn matches(&self, m: &IndexedMod) -> bool {
+        fn eq(filter: &Option<String>, value: Option<&str>) -> bool {
+            filter.as_deref().is_none_or(|x| Some(x) == value)
+        }
+        eq(&self.category, Some(&m.category))
+            && eq(&self.factorio_version, m.factorio_version.as_deref())
+            && eq(&self.license, m.license.as_deref())
+            && self.tag.as_ref().is_none_or(|x| m.tags.contains(x))
+            && self
+                .owner
+                .as_ref()
+                .is_none_or(|x| x.eq_ignore_ascii_case(&m.owner))
+            && self.deprecated.is_none_or(|x| x == m.deprecated)
+            && self.min_downloads.is_none_or(|x| m.downloads_count >= x)
+            && self.max_downloads.is_none_or(|x| m.downloads_count <= x)
+    }
+}
+
+/// Order of search results.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum SortBy {
+    /// most relevant first, and most downloaded for empty queries
+    #[default]
+    Relevance,
+    Downloads,
+    Score,
+    /// newest first
+    Created,
+    /// most recently updated first
+    Updated,
+    /// alphabetically
+    Title,
+}
+
+impl SortBy {
+    pub const ALL: &[SortBy] = &[
+        SortBy::Relevance,
+        SortBy::Downloads,
+        SortBy::Score,
+        SortBy::Created,
+        SortBy::Updated,
+        SortBy::Title,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortBy::Relevance => "relevance",
+            SortBy::Downloads => "downloads",
+            SortBy::Score => "score",
+            SortBy::Created => "created",
+            SortBy::Updated => "updated",
+            SortBy::Title => "title",
+        }
+    }
+
+    /// Sorts hits, keeping relevance order between equal ones.
+    pub fn sort(self, hits: &mut [SearchHit]) {
+        match self {
+            SortBy::Relevance => (),
+            SortBy::Downloads => {
+                hits.sort_by_key(|x| Reverse(x.m.downloads_count))
+            }
+            SortBy::Score => {
+                hits.sort_by(|a, b| b.m.score.total_cmp(&a.m.score))
+            }
+            SortBy::Created => {
+                hits.sort_by(|a, b| b.m.created_at.cmp(&a.m.created_at))
+            }
+            SortBy::Updated => {
+                hits.sort_by(|a, b| b.m.updated_at.cmp(&a.m.updated_at))
+            }
+            SortBy::Title => {
+                hits.sort_by_cached_key(|x| x.m.title.to_lowercase())
+            }
+        }
+    }
+}
+
+impl fmt::Display for SortBy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SortBy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SortBy::ALL
+            .iter()
+            .copied()
+            .find(|x| x.as_str() == s)
+            .ok_or_else(|| format!("unknown sort order `{s}`"))
+    }
+}
+
+pub struct SearchHit<'a> {
+    pub m: &'a IndexedMod,
+    /// relevance, higher is better; 0 for empty queries
+    pub rank: f32,
+}
+
+/// Number of mods with each value of filterable fields.
+#[derive(Debug, Default)]
+pub struct Facets {
+    pub categories: BTreeMap<String, usize>,
+    pub factorio_versions: BTreeMap<String, usize>,
+    pub licenses: BTreeMap<String, usize>,
+    pub deprecated: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexFile {
+    format: u32,
+    mods: Vec<IndexedMod>,
+}
+
+#[derive(Default)]
+pub struct SearchIndex {
+    mods: BTreeMap<String, IndexedMod>,
+    /// term -> names of mods containing it
+    postings: BTreeMap<String, BTreeSet<String>>,
+}
+
+fn modified_millis(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+        .map(|x| x.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Entries of `map` whose keys start with `prefix`.
+fn with_prefix<'a, V>(
+    map: &'a BTreeMap<String, V>,
+    prefix: &'a str,
+) -> impl Iterator<Item = (&'a String, &'a V)> {
+    map.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+        .take_while(move |(x, _)| x.starts_with(prefix))
+}
+
+impl SearchIndex {
+    /// Builds index of given mods from scratch.
+    pub fn build<'a>(mods: impl IntoIterator<Item = &'a ModFull>) -> Self {
+        let mut index = Self::default();
+        for m in mods {
+            index.insert(IndexedMod::new(m, 0));
+        }
+        index
+    }
+
+    fn insert(&mut self, m: IndexedMod) {
+        self.remove(&m.name.clone());
+        for term in m.terms.keys() {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(m.name.clone());
+        }
+        self.mods.insert(m.name.clone(), m);
+    }
+
+    fn remove(&mut self, name: &str) {
+        let Some(old) = self.mods.remove(name) else {
+            return;
+        };
+        for term in old.terms.keys() {
+            if let Some(names) = self.postings.get_mut(term) {
+                names.remove(name);
+                if names.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.mods.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mods.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&IndexedMod> {
+        self.mods.get(name)
+    }
+
+    pub fn mods(&self) -> impl Iterator<Item = &IndexedMod> {
+        self.mods.values()
+    }
+
+    /// Loads index saved in the cache, or empty one if there isn't any (or
+    /// it's in an old format).
+    pub fn load(cache: &Cache) -> Self {
+        let Ok(mut bytes) = fs::read(cache.root().join(INDEX_FILE)) else {
+            return Self::default();
+        };
+        let Ok(file) = simd_json::from_slice::<IndexFile>(&mut bytes) else {
+            return Self::default();
+        };
+        if file.format != INDEX_FORMAT {
+            return Self::default();
+        }
+        let mut index = Self::default();
+        for m in file.mods {
+            index.insert(m);
+        }
+        index
+    }
+
+    pub fn save(&self, cache: &Cache) -> Result<(), Error> {
+        let file = IndexFile {
+            format: INDEX_FORMAT,
+            mods: self.mods.values().cloned().collect(),
+        };
+        let bytes =
+            simd_json::to_vec(&file).map_err(|e| Error::CacheCorrupt {
+                path: cache.root().join(INDEX_FILE),
+                message: e.to_string(),
+            })?;
+        fs::write(cache.root().join(INDEX_FILE), bytes)?;
+        Ok(())
+    }
+
+    pub fn exists(cache: &Cache) -> bool {
+        cache.root().join(INDEX_FILE).exists()
+    }
+
+    /// Indexes cached mods that changed since index was saved, and removes
+    /// ones no longer in the cache.
+    ///
+    /// Returns whether anything changed, and errors of mods that couldn't be
+    /// loaded.
+    pub fn refresh(
+        &mut self,
+        cache: &Cache,
+    ) -> Result<(bool, Vec<Error>), Error> {
+        let mut errors = vec![];
+        let mut changed = false;
+        let mut cached = BTreeSet::new();
+        for entry in fs::read_dir(cache.mods_dir())? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some(name) =
+                file_name.to_str().and_then(|x| x.strip_suffix(".json"))
+            else {
+                continue;
+            };
+            cached.insert(name.to_string());
+            let modified = modified_millis(&entry.metadata()?);
+            if self.mods.get(name).is_some_and(|x| x.modified == modified) {
+                continue;
+            }
+            changed = true;
+            match cache.load_mod(name) {
+                Ok(m) => self.insert(IndexedMod::new(&m, modified)),
+                Err(e) => {
+                    self.remove(name);
+                    errors.push(e);
+                }
+            }
+        }
+        let removed: Vec<String> = self
+            .mods
+            .keys()
+            .filter(|x| !cached.contains(*x))
+            .cloned()
+            .collect();
+        changed |= !removed.is_empty();
+        for name in removed {
+            self.remove(&name);
+        }
+        Ok((changed, errors))
+    }
+
+    /// Loads saved index, brings it up to date with the cache, and saves it
+    /// if anything changed.
+    pub fn open(cache: &Cache) -> Result<(Self, Vec<Error>), Error> {
+        let mut index = Self::load(cache);
+        let (changed, errors) = index.refresh(cache)?;
+        if changed {
+            index.save(cache)?;
+        }
+        Ok((index, errors))
+    }
+
+    /// Returns relevance of a mod for given query terms, or `None` if any of
+    /// them doesn't match.
+    fn rank(&self, m: &IndexedMod, query: &[(String, f32)]) -> Option<f32> {
+        let mut rank = 0.0;
+        for (term, idf) in query {
+            let exact = m.terms.get(term).copied().unwrap_or_default();
+            let prefix: f32 = with_prefix(&m.terms, term)
+                .filter(|(x, _)| *x != term)
+                .map(|(_, &tf)| tf * PREFIX_WEIGHT)
+                .sum();
+            let tf = exact + prefix;
+            if tf == 0.0 {
+                return None;
+            }
+            // saturate, so long descriptions don't dominate
+            rank += idf * tf / (tf + 1.0);
+        }
+        Some(rank)
+    }
+
+    /// Searches mods matching all words of `query` and `filters`, ordered by
+    /// relevance. With empty query, all mods matching `filters` are returned,
+    /// ordered by download count.
+    pub fn search(&self, query: &str, filters: &Filters) -> Vec<SearchHit<'_>> {
+        let total = self.mods.len().max(1) as f32;
+        let query: Vec<(String, f32)> = tokenize(query)
+            .map(|term| {
+                let count = with_prefix(&self.postings, &term)
+                    .map(|(_, names)| names.len())
+                    .sum::<usize>()
+                    .max(1);
+                let idf = (total / count as f32).ln() + 1.0;
+                (term, idf)
+            })
+            .collect();
+
+        let mut hits: Vec<SearchHit> = self
+            .mods
+            .values()
+            .filter(|m| filters.matches(m))
+            .filter_map(|m| {
+                Some(SearchHit {
+                    m,
+                    rank: self.rank(m, &query)?,
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.rank
+                .total_cmp(&a.rank)
+                .then(b.m.downloads_count.cmp(&a.m.downloads_count))
+        });
+        hits
+    }
+
+    /// Counts values of filterable fields among given hits.
+    pub fn facets(hits: &[SearchHit]) -> Facets {
+        let mut facets = Facets::default();
+        for hit in hits {
+            let m = hit.m;
+            *facets.categories.entry(m.category.clone()).or_default() += 1;
+            if let Some(x) = &m.factorio_version {
+                *facets.factorio_versions.entry(x.clone()).or_default() += 1;
+            }
+            if let Some(x) = &m.license {
+                *facets.licenses.entry(x.clone()).or_default() += 1;
+            }
+            facets.deprecated += m.deprecated as usize;
+        }
+        facets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, time::Duration};
+
+    use super::*;
+
+    fn mod_full(json: &str) -> ModFull {
+        simd_json::from_slice(&mut json.as_bytes().to_vec()).unwrap()
+    }
+
+    fn index() -> SearchIndex {
+        let mods = [
+            r#"{"name": "belts", "title": "Fast belts", "owner": "alice",
+                "summary": "Faster transport belts", "category": "content",
+                "license": {"id": "default_mit"}, "downloads_count": 500,
+                "tags": ["logistics"],
+                "releases": [{"version": "1.0.0",
+                    "info_json": {"factorio_version": "2.0"}}]}"#,
+            r#"{"name": "trains", "title": "Trains", "owner": "bob",
+                "summary": "Trains that carry belts", "category": "content",
+                "license": {"id": "default_gnugplv3"}, "downloads_count": 100,
+                "releases": [{"version": "1.0.0",
+                    "info_json": {"factorio_version": "1.1"}}]}"#,
+            r#"{"name": "old-belts", "title": "Old belts", "owner": "bob",
+                "summary": "Belts", "category": "tweaks",
+                "downloads_count": 5000, "deprecated": true,
+                "releases": [{"version": "0.1.0",
+                    "info_json": {"factorio_version": "0.18"}}]}"#,
+        ];
+        SearchIndex::build(&mods.map(mod_full))
+    }
+
+    fn names(hits: &[SearchHit]) -> Vec<String> {
+        hits.iter().map(|x| x.m.name.clone()).collect()
+    }
+
+    #[test]
+    fn ranks_title_matches_before_summary_ones() {
+        let index = index();
+        let hits = index.search("belts", &Filters::default());
+        // "trains" only mentions belts in its summary, and equally relevant
+        // mods are ordered by downloads
+        assert_eq!(names(&hits), ["old-belts", "belts", "trains"]);
+        assert_eq!(hits[0].rank, hits[1].rank);
+        assert!(hits[1].rank > hits[2].rank);
+
+        // prefixes of words match too, but less
+        let hits = index.search("belt", &Filters::default());
+        assert_eq!(names(&hits), ["old-belts", "belts", "trains"]);
+        assert!(
+            hits[0].rank < index.search("belts", &Filters::default())[0].rank
+        );
+
+        // every word has to match
+        let hits = index.search("fast belts", &Filters::default());
+        assert_eq!(names(&hits), ["belts"]);
+        assert!(index.search("rockets", &Filters::default()).is_empty());
+    }
+
+    #[test]
+    fn empty_query_orders_by_downloads() {
+        let index = index();
+        let hits = index.search("", &Filters::default());
+        assert_eq!(names(&hits), ["old-belts", "belts", "trains"]);
+        assert!(hits.iter().all(|x| x.rank == 0.0));
+    }
+
+    #[test]
+    fn filters() {
+        let index = index();
+        let search = |filters: Filters| names(&index.search("", &filters));
+        assert_eq!(
+            search(Filters {
+                category: Some("tweaks".into()),
+                ..Filters::default()
+            }),
+            ["old-belts"]
+        );
+        assert_eq!(
+            search(Filters {
+                factorio_version: Some("1.1".into()),
+                ..Filters::default()
+            }),
+            ["trains"]
+        );
+        assert_eq!(
+            search(Filters {
+                license: Some("default_mit".into()),
+                ..Filters::default()
+            }),
+            ["belts"]
+        );
+        assert_eq!(
+            search(Filters {
+                deprecated: Some(false),
+                ..Filters::default()
+            }),
+            ["belts", "trains"]
+        );
+        assert_eq!(
+            search(Filters {
+                min_downloads: Some(100),
+                max_downloads: Some(500),
+                ..Filters::default()
+            }),
+            ["belts", "trains"]
+        );
+        assert_eq!(
+            search(Filters {
+                min_downloads: Some(501),
+                owner: Some("BOB".into()),
+                ..Filters::default()
+            }),
+            ["old-belts"]
+        );
+    }
+
+    #[test]
+    fn facets_count_hits() {
+        let index = index();
+        let hits = index.search("", &Filters::default());
+        let facets = SearchIndex::facets(&hits);
+        assert_eq!(facets.categories["content"], 2);
+        assert_eq!(facets.categories["tweaks"], 1);
+        assert_eq!(facets.factorio_versions["2.0"], 1);
+        assert_eq!(facets.licenses.len(), 2);
+        assert_eq!(facets.deprecated, 1);
+    }
+
+    #[test]
+    fn refresh_reindexes_files_with_changed_mtime() {
+        let root = std::env::temp_dir()
+            .join(format!("factorio-crater-search-{}", std::process::id()));
+        let cache = Cache::open(&root).unwrap();
+        let write = |name: &str, title: &str, modified_secs: u64| {
+            let path = cache.mod_path(name);
+            fs::write(
+                &path,
+                format!(r#"{{"name":"{name}","title":"{title}"}}"#),
+            )
+            .unwrap();
+            let modified = UNIX_EPOCH + Duration::from_secs(modified_secs);
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let titles = |index: &SearchIndex| {
+            index.mods().map(|x| x.title.clone()).collect::<Vec<_>>()
+        };
+        write("a", "First", 1000);
+        write("b", "Other", 1000);
+
+        let (index, errors) = SearchIndex::open(&cache).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(titles(&index), ["First", "Other"]);
+
+        let mut index = SearchIndex::load(&cache);
+        assert!(!index.refresh(&cache).unwrap().0);
+
+        // changed contents with the same mtime aren't noticed
+        write("a", "Second", 1000);
+        assert!(!index.refresh(&cache).unwrap().0);
+        assert_eq!(titles(&index), ["First", "Other"]);
+
+        write("a", "Third", 2000);
+        fs::remove_file(cache.mod_path("b")).unwrap();
+        assert!(index.refresh(&cache).unwrap().0);
+        assert_eq!(titles(&index), ["Third"]);
+        assert_eq!(names(&index.search("third", &Filters::default())), ["a"]);
+        assert!(index.search("other", &Filters::default()).is_empty());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
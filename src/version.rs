@@ -0,0 +1,187 @@
+use core::{fmt, str::FromStr};
+
+/// Mod or game version in factorio's `major.minor.sub` format.
+///
+/// Factorio versions in `info.json` only have two parts (`1.1`), in which case
+/// `sub` is 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u16,
+    pub minor: u16,
+    pub sub: u16,
+}
+
+impl Version {
+    pub const fn new(major: u16, minor: u16, sub: u16) -> Self {
+        Self { major, minor, sub }
+    }
+
+    /// Returns only the `major.minor` part, which is what factorio uses to
+    /// check mod compatibility with the game.
+    pub const fn factorio_version(self) -> Self {
+        Self::new(self.major, self.minor, 0)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.sub)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionError(pub String);
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid version: {:?}", self.0)
+    }
+}
+
+impl core::error::Error for ParseVersionError {}
+
+impl FromStr for Version {
+    type Err = ParseVersionError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseVersionError(s.to_string());
+        let mut parts = s.trim().split('.').map(|x| x.parse::<u16>());
+        let major = parts.next().ok_or_else(err)?.map_err(|_| err())?;
+        let minor = parts.next().ok_or_else(err)?.map_err(|_| err())?;
+        let sub = parts.next().transpose().map_err(|_| err())?.unwrap_or(0);
+        if parts.next().is_some() {
+            return Err(err());
+        }
+        Ok(Self::new(major, minor, sub))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum VersionOp {
+    Less,
+    LessEq,
+    Eq,
+    GreaterEq,
+    Greater,
+}
+
+impl VersionOp {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            VersionOp::Less => "<",
+            VersionOp::LessEq => "<=",
+            VersionOp::Eq => "=",
+            VersionOp::GreaterEq => ">=",
+            VersionOp::Greater => ">",
+        }
+    }
+}
+
+/// Version constraint of a dependency, like `>= 1.2.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VersionReq {
+    pub op: VersionOp,
+    pub version: Version,
+}
+
+impl VersionReq {
+    pub fn matches(&self, version: Version) -> bool {
+        match self.op {
+            VersionOp::Less => version < self.version,
+            VersionOp::LessEq => version <= self.version,
+            VersionOp::Eq => version == self.version,
+            VersionOp::GreaterEq => version >= self.version,
+            VersionOp::Greater => version > self.version,
+        }
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.op.as_str(), self.version)
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = ParseVersionError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        // longer operators first, so `<=` isn't parsed as `<`
+        let ops = [
+            VersionOp::LessEq,
+            VersionOp::GreaterEq,
+            VersionOp::Less,
+            VersionOp::Eq,
+            VersionOp::Greater,
+        ];
+        let (op, rest) = ops
+            .into_iter()
+            .find_map(|op| s.strip_prefix(op.as_str()).map(|rest| (op, rest)))
+            .ok_or_else(|| ParseVersionError(s.to_string()))?;
+        Ok(Self {
+            op,
+            version: rest.parse()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req(s: &str) -> VersionReq {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_version() {
+        assert_eq!("1.2.3".parse(), Ok(Version::new(1, 2, 3)));
+        assert_eq!(" 1.1 ".parse(), Ok(Version::new(1, 1, 0)));
+        assert!("1".parse::<Version>().is_err());
+        assert!("1.2.3.4".parse::<Version>().is_err());
+        assert!("1.x".parse::<Version>().is_err());
+        assert!("1.-2".parse::<Version>().is_err());
+        assert!("".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert!(Version::new(1, 10, 0) > Version::new(1, 9, 0));
+        assert!(Version::new(2, 0, 0) > Version::new(1, 99, 99));
+        assert_eq!(
+            Version::new(1, 1, 104).factorio_version(),
+            Version::new(1, 1, 0)
+        );
+        assert_eq!(Version::new(1, 1, 104).to_string(), "1.1.104");
+    }
+
+    #[test]
+    fn parse_version_req() {
+        assert_eq!(
+            req(">= 1.2"),
+            VersionReq {
+                op: VersionOp::GreaterEq,
+                version: Version::new(1, 2, 0),
+            }
+        );
+        // longer operators aren't cut short
+        assert_eq!(req("<=1.0.1").op, VersionOp::LessEq);
+        assert_eq!(req(" = 2.0 ").op, VersionOp::Eq);
+        assert_eq!(req("> 0.1").to_string(), "> 0.1.0");
+        assert!("1.0".parse::<VersionReq>().is_err());
+        assert!("=> 1.0".parse::<VersionReq>().is_err());
+        assert!(">= x".parse::<VersionReq>().is_err());
+    }
+
+    #[test]
+    fn version_req_matches() {
+        let v = Version::new;
+        assert!(req("< 1.1").matches(v(1, 0, 9)));
+        assert!(!req("< 1.1").matches(v(1, 1, 0)));
+        assert!(req("<= 1.1").matches(v(1, 1, 0)));
+        assert!(req("= 1.1").matches(v(1, 1, 0)));
+        assert!(!req("= 1.1").matches(v(1, 1, 1)));
+        assert!(req(">= 1.1").matches(v(1, 1, 0)));
+        assert!(!req("> 1.1").matches(v(1, 1, 0)));
+        assert!(req("> 1.1").matches(v(1, 1, 1)));
+    }
+}
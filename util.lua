@@ -17,9 +17,9 @@ end
 ---@return boolean
 table.all = function(table, f)
     for _, x in pairs(table) do
-        if f(x) then
-            return true
+        if not f(x) then
+            return false
         end
     end
-    return false
+    return true
 end